@@ -0,0 +1,75 @@
+//! Command-line arguments shared by the programs of Vaporetto.
+
+use clap::Args;
+use vaporetto::{errors::VaporettoError, CorpusFormat, TokenizedFormat};
+
+/// Arguments specifying separators of the tokenized format.
+#[derive(Args, Debug)]
+pub struct TokenizedFormatArgs {
+    /// The character separating tokens in the tokenized format. "\t" is interpreted as a tab
+    #[arg(long, default_value = " ", value_parser = parse_char)]
+    pub token_separator: char,
+
+    /// The character separating a surface and tags in the tokenized format. "\t" is
+    /// interpreted as a tab
+    #[arg(long, default_value = "/", value_parser = parse_char)]
+    pub tag_separator: char,
+
+    /// The character escaping separators in the tokenized format
+    #[arg(long, default_value = "\\", value_parser = parse_char)]
+    pub escape_char: char,
+}
+
+impl TokenizedFormatArgs {
+    /// Returns the tokenized format specified by the arguments.
+    pub fn tokenized_format(&self) -> Result<TokenizedFormat, VaporettoError> {
+        TokenizedFormat::new(self.token_separator, self.tag_separator, self.escape_char)
+    }
+}
+
+/// Names of the corpus formats.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum CorpusFormatKind {
+    Tokenized,
+    Conllu,
+    Tsv,
+}
+
+/// Arguments specifying the format of input corpora.
+#[derive(Args, Debug)]
+pub struct CorpusFormatArgs {
+    /// The format of the input corpora: {tokenized, conllu, tsv}.
+    /// tokenized: one sentence per line, tokens separated by spaces and tags following slashes.
+    /// conllu: the CoNLL-U format, where UPOS and XPOS are used as tags.
+    /// tsv: one token per line followed by tab-separated tags, and sentences separated by empty
+    /// lines.
+    #[arg(long, default_value = "tokenized")]
+    pub format: CorpusFormatKind,
+
+    #[command(flatten)]
+    pub tokenized: TokenizedFormatArgs,
+}
+
+impl CorpusFormatArgs {
+    /// Returns the corpus format specified by the arguments.
+    pub fn corpus_format(&self) -> Result<CorpusFormat, VaporettoError> {
+        Ok(match self.format {
+            CorpusFormatKind::Tokenized => {
+                CorpusFormat::Tokenized(self.tokenized.tokenized_format()?)
+            }
+            CorpusFormatKind::Conllu => CorpusFormat::Conllu,
+            CorpusFormatKind::Tsv => CorpusFormat::Tsv,
+        })
+    }
+}
+
+fn parse_char(s: &str) -> Result<char, String> {
+    if s == "\\t" {
+        return Ok('\t');
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err("must be a single character".to_string()),
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use cli_args::CorpusFormatArgs;
+use vaporetto::{CorpusFormat, DiffKind, Sentence, TokenDiff, TokenizedFormat};
+
+const COLOR_REFERENCE: &str = "\x1b[31m";
+const COLOR_SYSTEM: &str = "\x1b[32m";
+const COLOR_TAG: &str = "\x1b[33m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "A program to compare two segmentations of the same texts.")]
+struct Args {
+    /// The reference corpus, such as a gold corpus or an output of a model
+    #[arg(long)]
+    reference: PathBuf,
+
+    /// The corpus compared with the reference, such as an output of another model
+    #[arg(long)]
+    system: PathBuf,
+
+    #[command(flatten)]
+    corpus_format: CorpusFormatArgs,
+
+    /// Compares only word boundaries.
+    #[arg(long)]
+    ignore_tags: bool,
+
+    /// Colors differences: {auto, always, never}.
+    /// auto: colors differences only when the output is a terminal.
+    #[arg(long, default_value = "auto")]
+    color: ColorMode,
+}
+
+fn read_corpus(
+    path: &Path,
+    format: &CorpusFormat,
+) -> Result<Vec<Sentence<'static, 'static>>, Box<dyn std::error::Error>> {
+    let f = BufReader::new(File::open(path)?);
+    let mut sentences = vec![];
+    for record in format.records(f) {
+        sentences.push(Sentence::from_format(&record?, format)?);
+    }
+    Ok(sentences)
+}
+
+/// Splits a text in the tokenized format at unescaped token separators.
+fn split_tokens<'a>(text: &'a str, format: &TokenizedFormat) -> Vec<&'a str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == format.escape() {
+            escaped = true;
+        } else if c == format.token_separator() {
+            tokens.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    tokens.push(&text[start..]);
+    tokens
+}
+
+/// Writes the sentence in a line in the tokenized format, highlighting tokens overlapping with
+/// differences.
+fn write_tokens(
+    mut out: impl Write,
+    prefix: char,
+    s: &Sentence,
+    diffs: &[TokenDiff],
+    color: Option<&str>,
+    format: &TokenizedFormat,
+) -> io::Result<()> {
+    let mut buf = String::new();
+    s.write_tokenized_text_with_format(&mut buf, format);
+    write!(out, "{prefix}")?;
+    for (token, text) in s.iter_tokens().zip(split_tokens(&buf, format)) {
+        let sep = format.token_separator();
+        let diff = diffs
+            .iter()
+            .find(|d| d.start < token.end() && token.start() < d.end);
+        match (diff, color) {
+            (Some(diff), Some(color)) => {
+                let color = if diff.kind == DiffKind::TagMismatch {
+                    COLOR_TAG
+                } else {
+                    color
+                };
+                write!(out, "{sep}{color}{text}{COLOR_RESET}")?;
+            }
+            _ => write!(out, "{sep}{text}")?,
+        }
+    }
+    writeln!(out)
+}
+
+const fn kind_name(kind: DiffKind) -> &'static str {
+    match kind {
+        DiffKind::OverSplit => "over-split",
+        DiffKind::UnderSplit => "under-split",
+        DiffKind::Crossing => "crossing",
+        DiffKind::TagMismatch => "tag mismatch",
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let colored = match args.color {
+        ColorMode::Auto => atty::is(atty::Stream::Stdout),
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+    };
+
+    let corpus_format = args.corpus_format.corpus_format()?;
+    let tokenized_format = args.corpus_format.tokenized.tokenized_format()?;
+    let refs = read_corpus(&args.reference, &corpus_format)?;
+    let syss = read_corpus(&args.system, &corpus_format)?;
+    if refs.len() != syss.len() {
+        return Err(format!(
+            "the number of sentences is different: {} and {}",
+            refs.len(),
+            syss.len()
+        )
+        .into());
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut n_diff_sentences = 0;
+    let mut n_diffs = [0; 4];
+    for (i, (r, s)) in refs.iter().zip(&syss).enumerate() {
+        let mut diffs = r.diff(s).map_err(|e| format!("sentence {}: {e}", i + 1))?;
+        if args.ignore_tags {
+            diffs.retain(|d| d.kind != DiffKind::TagMismatch);
+        }
+        if diffs.is_empty() {
+            continue;
+        }
+        n_diff_sentences += 1;
+        writeln!(out, "# sentence {}", i + 1)?;
+        write_tokens(
+            &mut out,
+            '-',
+            r,
+            &diffs,
+            colored.then_some(COLOR_REFERENCE),
+            &tokenized_format,
+        )?;
+        write_tokens(
+            &mut out,
+            '+',
+            s,
+            &diffs,
+            colored.then_some(COLOR_SYSTEM),
+            &tokenized_format,
+        )?;
+        for diff in &diffs {
+            n_diffs[diff.kind as usize] += 1;
+            let span: String = r
+                .as_raw_text()
+                .chars()
+                .skip(diff.start)
+                .take(diff.end - diff.start)
+                .collect();
+            writeln!(
+                out,
+                "  {} {}..{}: {span}",
+                kind_name(diff.kind),
+                diff.start,
+                diff.end,
+            )?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(
+        out,
+        "Sentences: {}, with differences: {n_diff_sentences}",
+        refs.len()
+    )?;
+    writeln!(
+        out,
+        "Over-split: {}, Under-split: {}, Crossing: {}, Tag mismatch: {}",
+        n_diffs[DiffKind::OverSplit as usize],
+        n_diffs[DiffKind::UnderSplit as usize],
+        n_diffs[DiffKind::Crossing as usize],
+        n_diffs[DiffKind::TagMismatch as usize],
+    )?;
+
+    Ok(())
+}
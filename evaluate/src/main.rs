@@ -1,10 +1,11 @@
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use clap::Parser;
-use vaporetto::{CharacterBoundary, CharacterType, Model, Predictor, Sentence};
+use cli_args::CorpusFormatArgs;
+use vaporetto::{CharacterBoundary, CharacterType, Model, Predictor, Sentence, TagPrediction};
 use vaporetto_rules::{
     sentence_filters::{ConcatGraphemeClustersFilter, KyteaWsConstFilter},
     string_filters::KyteaFullwidthFilter,
@@ -50,6 +51,10 @@ struct Args {
     #[arg(long)]
     predict_tags: bool,
 
+    /// Predicts POS tags of out-of-vocabulary tokens as well. This implies --predict-tags.
+    #[arg(long)]
+    predict_oov_tags: bool,
+
     /// Do not segment some character types: {D, R, H, T, K, O, G}.
     /// D: Digit, R: Roman, H: Hiragana, T: Katakana, K: Kanji, O: Other, G: Grapheme cluster.
     #[arg(long)]
@@ -64,10 +69,14 @@ struct Args {
     /// word: evaluates each word using Nagata's method.
     #[arg(long, default_value = "char")]
     metric: EvaluationMetric,
+
+    #[command(flatten)]
+    corpus_format: CorpusFormatArgs,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    let corpus_format = args.corpus_format.corpus_format()?;
 
     let fullwidth_filter = KyteaFullwidthFilter;
     let mut post_filters: Vec<Box<dyn SentenceFilter>> = vec![];
@@ -83,30 +92,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("Loading model file...");
     let mut f = zstd::Decoder::new(File::open(args.model)?)?;
     let model = Model::read(&mut f)?;
-    let predictor = Predictor::new(model, args.predict_tags)?;
+    let tag_prediction = if args.predict_oov_tags {
+        TagPrediction::WithOov
+    } else {
+        TagPrediction::from(args.predict_tags)
+    };
+    let predictor = Predictor::new(model, tag_prediction)?;
 
     eprintln!("Start tokenization");
 
     let mut results = vec![];
-    let lines = io::stdin().lock().lines();
-    for line in lines {
-        let line = line?;
-        if line.is_empty() {
+    for record in corpus_format.records(io::stdin().lock()) {
+        let record = record?;
+        if record.is_empty() {
             continue;
         }
-        let mut s = Sentence::from_tokenized(&line)?;
+        let mut s = Sentence::from_format(&record, &corpus_format)?;
         let ref_boundaries = s.boundaries().to_vec();
         let mut ref_tags = vec![];
         for i in 0..=ref_boundaries.len() {
             ref_tags.push(s.tags()[i * s.n_tags()..(i + 1) * s.n_tags()].to_vec());
         }
         if !args.no_norm {
+            let preserves_whitespace = s.preserves_whitespace();
             let new_line = fullwidth_filter.filter(s.as_raw_text());
-            s = Sentence::from_raw(new_line)?
+            s = Sentence::from_raw(new_line)?;
+            // Spaces between words in the CoNLL-U format are not tokens.
+            if preserves_whitespace {
+                s.preserve_whitespace()?;
+            }
         };
         predictor.predict(&mut s);
         post_filters.iter().for_each(|filter| filter.filter(&mut s));
-        if args.predict_tags {
+        if tag_prediction != TagPrediction::Disabled {
             s.fill_tags();
         }
         let sys_boundaries = s.boundaries().to_vec();
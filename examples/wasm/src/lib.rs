@@ -4,6 +4,7 @@ pub mod text_input;
 pub mod token_view;
 
 use std::io::Read;
+use std::mem;
 use std::rc::Rc;
 
 use gloo_worker::{HandlerId, Spawnable, Worker, WorkerBridge, WorkerScope};
@@ -30,18 +31,14 @@ pub struct WorkerMessage {
     pub output: (Vec<Token>, usize),
 }
 
-#[ouroboros::self_referencing]
 pub struct VaporettoWorker {
     predictor: Predictor,
     wsconst_g: ConcatGraphemeClustersFilter,
     wsconst_d: KyteaWsConstFilter,
 
-    #[borrows(predictor)]
-    #[covariant]
-    sentence_orig: Sentence<'static, 'this>,
-    #[borrows(predictor)]
-    #[covariant]
-    sentence_filtered: Sentence<'static, 'this>,
+    // Sentences are kept to reuse their buffers.
+    sentence_orig: Sentence<'static, 'static>,
+    sentence_filtered: Sentence<'static, 'static>,
 }
 
 impl Worker for VaporettoWorker {
@@ -55,14 +52,13 @@ impl Worker for VaporettoWorker {
         let mut buff = vec![];
         decoder.read_to_end(&mut buff).unwrap();
         let (model, _) = Model::read_slice(&buff).unwrap();
-        VaporettoWorkerBuilder {
+        Self {
             predictor: Predictor::new(model, true).unwrap(),
             wsconst_g: ConcatGraphemeClustersFilter,
             wsconst_d: KyteaWsConstFilter::new(CharacterType::Digit),
-            sentence_orig_builder: |_| Sentence::default(),
-            sentence_filtered_builder: |_| Sentence::default(),
+            sentence_orig: Sentence::default(),
+            sentence_filtered: Sentence::default(),
         }
-        .build()
     }
 
     fn update(&mut self, scope: &WorkerScope<Self>, msg: Self::Message) {
@@ -82,29 +78,26 @@ impl Worker for VaporettoWorker {
             return;
         }
 
-        self.with_mut(|fields| {
-            fields.sentence_filtered.update_raw(filtered_text).unwrap();
-            fields.predictor.predict(fields.sentence_filtered);
-            fields.wsconst_g.filter(fields.sentence_filtered);
-            fields.wsconst_d.filter(fields.sentence_filtered);
-            fields.sentence_filtered.fill_tags();
-
-            fields.sentence_orig.update_raw(msg).unwrap();
-
-            let n_tags = fields.sentence_filtered.n_tags();
-            fields
-                .sentence_orig
-                .boundaries_mut()
-                .copy_from_slice(fields.sentence_filtered.boundaries());
-            fields.sentence_orig.reset_tags(n_tags);
-            fields
-                .sentence_orig
-                .tags_mut()
-                .clone_from_slice(fields.sentence_filtered.tags());
-        });
-
-        let tokens = self
-            .borrow_sentence_orig()
+        let mut sentence_filtered = mem::take(&mut self.sentence_filtered);
+        sentence_filtered.update_raw(filtered_text).unwrap();
+        self.predictor.predict(&mut sentence_filtered);
+        self.wsconst_g.filter(&mut sentence_filtered);
+        self.wsconst_d.filter(&mut sentence_filtered);
+        sentence_filtered.fill_tags();
+
+        let mut sentence_orig = mem::take(&mut self.sentence_orig);
+        sentence_orig.update_raw(msg).unwrap();
+
+        let n_tags = sentence_filtered.n_tags();
+        sentence_orig
+            .boundaries_mut()
+            .copy_from_slice(sentence_filtered.boundaries());
+        sentence_orig.reset_tags(n_tags);
+        sentence_orig
+            .tags_mut()
+            .clone_from_slice(sentence_filtered.tags());
+
+        let tokens = sentence_orig
             .iter_tokens()
             .map(|token| Token {
                 surface: token.surface().to_string(),
@@ -119,7 +112,9 @@ impl Worker for VaporettoWorker {
                     .collect(),
             })
             .collect();
-        let n_tags = self.borrow_sentence_orig().n_tags();
+
+        self.sentence_orig = sentence_orig.into_owned();
+        self.sentence_filtered = sentence_filtered.into_owned();
 
         let output = (tokens, n_tags);
         scope.send_message(WorkerMessage { id, output })
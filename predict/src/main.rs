@@ -1,15 +1,23 @@
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{self, BufRead, BufWriter, Write};
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Instant;
 
 use clap::Parser;
-use vaporetto::{CharacterType, Model, Predictor, Sentence};
+use cli_args::TokenizedFormatArgs;
+use serde::Serialize;
+use vaporetto::{
+    CharacterType, ConflictResolution, Model, Predictor, Sentence, TagPrediction, TokenizedFormat,
+    UserDictionary, UserWord,
+};
 use vaporetto_rules::{
     sentence_filters::{ConcatGraphemeClustersFilter, KyteaWsConstFilter},
+    sentence_splitters::PunctuationSplitter,
     string_filters::KyteaFullwidthFilter,
-    SentenceFilter, StringFilter,
+    SentenceFilter, SentenceSplitter, StringFilter,
 };
 
 #[derive(Clone, Debug)]
@@ -34,6 +42,118 @@ impl FromStr for WsConst {
     }
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Tokenized,
+    Conllu,
+    Tsv,
+    Mecab,
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// Writes the sentence followed by a line break. The JSON Lines format is written for each
+    /// input line by [`JsonLine`] instead.
+    fn write(
+        self,
+        s: &Sentence,
+        tokenized_format: &TokenizedFormat,
+        mecab_features: &[Option<usize>],
+        buf: &mut String,
+    ) {
+        match self {
+            Self::Tokenized => {
+                s.write_tokenized_text_with_format(buf, tokenized_format);
+                buf.push('\n');
+            }
+            Self::Conllu => s.write_conllu(buf),
+            Self::Tsv => s.write_tsv(buf),
+            Self::Mecab => s.write_mecab_text(buf, mecab_features),
+            Self::Jsonl => unreachable!("JSON Lines are written for each input line"),
+        }
+    }
+
+    /// Writes the output of a line that cannot be tokenized, such as an empty line. In the MeCab
+    /// format, `EOS` is written so that each input line corresponds to an `EOS`.
+    fn write_empty(self, buf: &mut String) {
+        buf.clear();
+        match self {
+            Self::Tokenized => buf.push('\n'),
+            Self::Mecab => buf.push_str("EOS\n"),
+            Self::Conllu | Self::Tsv => (),
+            Self::Jsonl => unreachable!("JSON Lines are written for each input line"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonToken {
+    surface: String,
+    /// Offsets in characters.
+    start: usize,
+    end: usize,
+    /// Offsets in bytes of the UTF-8 text.
+    byte_start: usize,
+    byte_end: usize,
+    tags: Vec<Option<String>>,
+}
+
+/// Output of an input line in the JSON Lines format.
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    text: &'a str,
+    tokens: Vec<JsonToken>,
+    /// Scores of character boundaries of the line, which are `null` outside of sentences.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    boundary_scores: Option<Vec<Option<i32>>>,
+}
+
+impl<'a> JsonLine<'a> {
+    fn new(text: &'a str, scores: bool) -> Self {
+        let n_boundaries = text.chars().count().saturating_sub(1);
+        Self {
+            text,
+            tokens: vec![],
+            boundary_scores: scores.then(|| vec![None; n_boundaries]),
+        }
+    }
+
+    /// Appends tokens and boundary scores of a sentence starting at the given offsets of the line.
+    fn push(&mut self, s: &Sentence, scores: &[i32], start: usize, byte_start: usize) {
+        let text = s.as_raw_text();
+        let mut byte_offsets: Vec<_> = text.char_indices().map(|(i, _)| byte_start + i).collect();
+        byte_offsets.push(byte_start + text.len());
+        self.tokens.extend(s.iter_tokens().map(|token| {
+            JsonToken {
+                surface: token.surface().to_string(),
+                start: start + token.start(),
+                end: start + token.end(),
+                byte_start: byte_offsets[token.start()],
+                byte_end: byte_offsets[token.end()],
+                tags: token
+                    .tags()
+                    .iter()
+                    .map(|tag| tag.as_deref().map(str::to_string))
+                    .collect(),
+            }
+        }));
+        if let Some(boundary_scores) = self.boundary_scores.as_mut() {
+            for (dst, &score) in boundary_scores[start..].iter_mut().zip(scores) {
+                dst.replace(score);
+            }
+        }
+    }
+}
+
+fn parse_mecab_feature(s: &str) -> Result<Option<usize>, String> {
+    if s == "*" {
+        return Ok(None);
+    }
+    s.parse()
+        .map(Some)
+        .map_err(|_| "must be a tag index or '*'".to_string())
+}
+
 #[derive(Parser, Debug)]
 #[command(about = "A program to perform word segmentation.")]
 struct Args {
@@ -45,18 +165,79 @@ struct Args {
     #[arg(long)]
     predict_tags: bool,
 
+    /// Predicts POS tags of out-of-vocabulary tokens as well. This implies --predict-tags.
+    #[arg(long)]
+    predict_oov_tags: bool,
+
+    /// Revises word boundaries whose absolute scores are smaller than this value using tag
+    /// models. This implies --predict-tags.
+    #[arg(long)]
+    joint_margin: Option<u32>,
+
+    /// The beam width used when revising word boundaries.
+    #[arg(long, default_value = "8")]
+    joint_beam_width: usize,
+
+    /// The weight of tag confidences used when revising word boundaries.
+    #[arg(long, default_value = "1.0")]
+    joint_tag_weight: f64,
+
+    /// The penalty of each token in the scale of raw tag scores used when revising word
+    /// boundaries. Larger values produce fewer tokens.
+    #[arg(long, default_value = "1.0")]
+    joint_token_penalty: f64,
+
+    /// A user dictionary file in the CSV or TSV format. Each row contains a word and its tags.
+    /// Words in the dictionary are forced to be single tokens.
+    #[arg(long)]
+    user_dict: Option<PathBuf>,
+
+    /// Prefers words appearing first in the user dictionary rather than the longest words.
+    #[arg(long)]
+    user_dict_first_match: bool,
+
     /// Do not segment some character types: {D, R, H, T, K, O, G}.
     /// D: Digit, R: Roman, H: Hiragana, T: Katakana, K: Kanji, O: Other, G: Grapheme cluster.
     #[arg(long)]
     wsconst: Vec<WsConst>,
 
-    /// Prints scores.
+    /// Prints scores. In the JSON Lines format, scores are written in the "boundary_scores" field.
     #[arg(long)]
     scores: bool,
 
+    /// The output format: {tokenized, conllu, tsv, mecab, jsonl}.
+    /// tokenized: one sentence per line, tokens separated by spaces and tags following slashes.
+    /// conllu: the CoNLL-U format, where the first and second tags are written as UPOS and XPOS.
+    /// tsv: one token per line followed by tab-separated tags, and sentences separated by empty
+    /// lines.
+    /// mecab: the output format of MeCab, where tags are written as features.
+    /// jsonl: a JSON object per line containing tokens with their offsets and tags.
+    #[arg(long, default_value = "tokenized")]
+    output_format: OutputFormat,
+
+    #[command(flatten)]
+    tokenized_format: TokenizedFormatArgs,
+
+    /// Comma-separated tag indices (0-origin) written in feature columns of the MeCab format,
+    /// e.g., "0,*,*,*,*,*,*,1" ('*' writes an empty feature). By default, all tags are written in
+    /// order.
+    #[arg(long, value_delimiter = ',', value_parser = parse_mecab_feature)]
+    mecab_features: Vec<Option<usize>>,
+
     /// Do not normalize input strings before prediction.
     #[arg(long)]
     no_norm: bool,
+
+    /// Treats whitespace in input strings as word boundaries and excludes it from tokens.
+    /// Offsets in the JSON Lines format refer to the original input.
+    #[arg(long)]
+    preserve_whitespace: bool,
+
+    /// Splits each input line into sentences at punctuation marks such as "。", "！", and "？",
+    /// and writes the result of each sentence separately. In the JSON Lines format, sentences of
+    /// a line are written in the same object, and boundary scores between sentences are null.
+    #[arg(long)]
+    split_sentences: bool,
 }
 
 fn print_scores(s: &Sentence, mut out: impl Write) -> Result<(), Box<dyn std::error::Error>> {
@@ -70,8 +251,44 @@ fn print_scores(s: &Sentence, mut out: impl Write) -> Result<(), Box<dyn std::er
     Ok(())
 }
 
+/// Returns byte ranges of sentences in the line, or the whole line if `splitter` is `None`.
+fn split_line(line: &str, splitter: Option<&PunctuationSplitter>) -> Vec<Range<usize>> {
+    match splitter {
+        Some(splitter) => splitter.split(line),
+        None => std::iter::once(0..line.len()).collect(),
+    }
+}
+
+fn read_user_dictionary(
+    path: &Path,
+    resolution: ConflictResolution,
+) -> Result<UserDictionary, Box<dyn std::error::Error>> {
+    let delimiter = if path.extension() == Some(OsStr::new("tsv")) {
+        b'\t'
+    } else {
+        b','
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_path(path)?;
+    let mut words = vec![];
+    for result in rdr.records() {
+        let record = result?;
+        let mut fields = record.iter();
+        let word = fields.next().unwrap_or_default().to_string();
+        let tags = fields
+            .map(|tag| (!tag.is_empty()).then(|| tag.to_string()))
+            .collect();
+        words.push(UserWord::new(word, tags)?);
+    }
+    Ok(UserDictionary::new(words, resolution)?)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    let tokenized_format = args.tokenized_format.tokenized_format()?;
 
     let pre_filter = KyteaFullwidthFilter;
     let mut post_filters: Vec<Box<dyn SentenceFilter>> = vec![];
@@ -87,7 +304,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("Loading model file...");
     let mut f = zstd::Decoder::new(File::open(args.model)?)?;
     let model = Model::read(&mut f)?;
-    let predictor = Predictor::new(model, args.predict_tags)?;
+    let tag_prediction = if args.predict_oov_tags {
+        TagPrediction::WithOov
+    } else {
+        TagPrediction::from(args.predict_tags || args.joint_margin.is_some())
+    };
+    let mut predictor = Predictor::new(model, tag_prediction)?;
+    if let Some(path) = args.user_dict.as_ref() {
+        eprintln!("Loading user dictionary...");
+        let resolution = if args.user_dict_first_match {
+            ConflictResolution::FirstMatch
+        } else {
+            ConflictResolution::LongestMatch
+        };
+        predictor.set_user_dictionary(Some(read_user_dictionary(path, resolution)?));
+    }
 
     let is_tty = atty::is(atty::Stream::Stdout);
 
@@ -95,58 +326,88 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut out = BufWriter::new(io::stdout().lock());
     let mut buf = String::new();
     let mut s = Sentence::default();
+    let splitter = args.split_sentences.then(PunctuationSplitter::default);
 
     let start = Instant::now();
-    if args.no_norm {
-        let lines = io::stdin().lock().lines();
-        for line in lines {
-            let line = line?;
-            if s.update_raw(line).is_ok() {
-                predictor.predict(&mut s);
-                post_filters.iter().for_each(|filter| filter.filter(&mut s));
-                if args.predict_tags {
-                    s.fill_tags();
-                }
-                s.write_tokenized_text(&mut buf);
-                out.write_all(buf.as_bytes())?;
-                if args.scores {
-                    print_scores(&s, &mut out)?;
+    let mut s_orig = Sentence::default();
+    let is_jsonl = args.output_format == OutputFormat::Jsonl;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let ranges = split_line(&line, splitter.as_ref());
+        let mut json_line = JsonLine::new(&line, args.scores);
+        if ranges.is_empty() && !is_jsonl {
+            args.output_format.write_empty(&mut buf);
+            out.write_all(buf.as_bytes())?;
+        }
+        // The start position of the current sentence in characters.
+        let mut char_start = 0;
+        let mut byte_pos = 0;
+        for range in ranges {
+            char_start += line[byte_pos..range.start].chars().count();
+            byte_pos = range.start;
+            let text = &line[range];
+            let input = if args.no_norm {
+                text.to_string()
+            } else {
+                pre_filter.filter(text)
+            };
+            if s.update_raw(input).is_err() {
+                if !is_jsonl {
+                    args.output_format.write_empty(&mut buf);
+                    out.write_all(buf.as_bytes())?;
                 }
+                continue;
             }
-            out.write_all(b"\n")?;
-            if is_tty {
-                out.flush()?;
+            if args.preserve_whitespace {
+                s.preserve_whitespace()?;
             }
-        }
-    } else {
-        let mut s_orig = Sentence::default();
-        let lines = io::stdin().lock().lines();
-        for line in lines {
-            let line = line?;
-            let line_preproc = pre_filter.filter(&line);
-            if s.update_raw(line_preproc).is_ok() {
+            if let Some(margin) = args.joint_margin {
+                predictor.predict_joint(
+                    &mut s,
+                    margin,
+                    args.joint_beam_width,
+                    args.joint_tag_weight,
+                    args.joint_token_penalty,
+                );
+            } else {
                 predictor.predict(&mut s);
-                post_filters.iter().for_each(|filter| filter.filter(&mut s));
-                if args.predict_tags {
-                    s.fill_tags();
-                }
-                s_orig.update_raw(line)?;
+            }
+            post_filters.iter().for_each(|filter| filter.filter(&mut s));
+            if tag_prediction != TagPrediction::Disabled {
+                s.fill_tags();
+            }
+            // Restores the original text if the input is normalized.
+            let s_out = if args.no_norm {
+                &s
+            } else {
+                s_orig.update_raw(text.to_string())?;
                 s_orig.reset_tags(s.n_tags());
                 s_orig.boundaries_mut().copy_from_slice(s.boundaries());
                 s_orig.tags_mut().clone_from_slice(s.tags());
-                s_orig.write_tokenized_text(&mut buf);
+                if args.preserve_whitespace {
+                    s_orig.preserve_whitespace()?;
+                }
+                &s_orig
+            };
+            if is_jsonl {
+                json_line.push(s_out, s.boundary_scores(), char_start, byte_pos);
+            } else {
+                args.output_format
+                    .write(s_out, &tokenized_format, &args.mecab_features, &mut buf);
                 out.write_all(buf.as_bytes())?;
-                out.write_all(b"\n")?;
                 if args.scores {
                     print_scores(&s, &mut out)?;
                 }
-            } else {
-                out.write_all(b"\n")?;
-            }
-            if is_tty {
-                out.flush()?;
             }
         }
+        if is_jsonl {
+            buf = serde_json::to_string(&json_line)?;
+            buf.push('\n');
+            out.write_all(buf.as_bytes())?;
+        }
+        if is_tty {
+            out.flush()?;
+        }
     }
 
     let duration = start.elapsed();
@@ -0,0 +1,91 @@
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use vaporetto::{CharacterBoundary, Model, Predictor, Sentence};
+use vaporetto_rules::{string_filters::KyteaFullwidthFilter, StringFilter};
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "A program to select character boundaries that should be annotated for active learning."
+)]
+struct Args {
+    /// The model file to use when analyzing text
+    #[arg(long)]
+    model: PathBuf,
+
+    /// The number of character boundaries to select
+    #[arg(long)]
+    n_boundaries: usize,
+
+    /// Do not normalize input strings before prediction.
+    #[arg(long)]
+    no_norm: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let pre_filter = KyteaFullwidthFilter;
+
+    eprintln!("Loading model file...");
+    let mut f = zstd::Decoder::new(File::open(args.model)?)?;
+    let model = Model::read(&mut f)?;
+    let predictor = Predictor::new(model, false)?;
+
+    eprintln!("Start prediction");
+    let mut lines = vec![];
+    let mut predictions = vec![];
+
+    // Keeps the boundaries with the smallest absolute scores in a max-heap.
+    let mut candidates = BinaryHeap::new();
+
+    let mut s = Sentence::default();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line_idx = lines.len();
+        let input = if args.no_norm {
+            line.clone()
+        } else {
+            pre_filter.filter(&line)
+        };
+        if s.update_raw(input).is_ok() {
+            predictor.predict(&mut s);
+            for (i, &score) in s.boundary_scores().iter().enumerate() {
+                candidates.push((score.unsigned_abs(), line_idx, i));
+                if candidates.len() > args.n_boundaries {
+                    candidates.pop();
+                }
+            }
+            predictions.push(Some(s.boundaries().to_vec()));
+        } else {
+            predictions.push(None);
+        }
+        lines.push(line);
+    }
+
+    let mut n_selected = vec![0; lines.len()];
+    for &(_, line_idx, i) in &candidates {
+        predictions[line_idx].as_mut().unwrap()[i] = CharacterBoundary::Unknown;
+        n_selected[line_idx] += 1;
+    }
+    eprintln!("Selected {} boundaries", candidates.len());
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut buf = String::new();
+    for ((line, boundaries), n_selected) in lines.into_iter().zip(predictions).zip(n_selected) {
+        // Outputs only sentences that contain boundaries to be annotated.
+        if n_selected == 0 {
+            continue;
+        }
+        let mut s = Sentence::from_raw(line)?;
+        s.boundaries_mut().copy_from_slice(&boundaries.unwrap());
+        s.write_partial_annotation_text(&mut buf);
+        out.write_all(buf.as_bytes())?;
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
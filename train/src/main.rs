@@ -1,10 +1,13 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
-use std::io::{prelude::*, stderr, BufReader};
+use std::io::{prelude::*, stderr, BufRead, BufReader};
 use std::path::PathBuf;
 
 use clap::{ArgGroup, Parser};
-use vaporetto::{Sentence, SolverType, Trainer};
+use cli_args::CorpusFormatArgs;
+use vaporetto::{
+    FeatureTemplate, Sentence, SolverType, Trainer, TrainingObserver, TrainingProgress,
+};
 use vaporetto_rules::{string_filters::KyteaFullwidthFilter, StringFilter};
 
 #[derive(Parser, Debug)]
@@ -13,17 +16,30 @@ use vaporetto_rules::{string_filters::KyteaFullwidthFilter, StringFilter};
     group = ArgGroup::new("dataset").required(true).multiple(true),
 )]
 struct Args {
-    /// A tokenized training corpus
-    #[arg(long, group = "dataset")]
-    tok: Vec<PathBuf>,
+    /// A tokenized training corpus. A sample weight can be specified in the form of PATH:WEIGHT.
+    /// Examples are copied in proportion to the weights, so weights with a small integer ratio,
+    /// such as 1 and 2, keep training fast
+    #[arg(long, group = "dataset", value_parser = parse_weighted_path)]
+    tok: Vec<(PathBuf, f64)>,
 
-    /// A partially annotated training corpus
-    #[arg(long, group = "dataset")]
-    part: Vec<PathBuf>,
+    #[command(flatten)]
+    corpus_format: CorpusFormatArgs,
 
-    /// A word dictionary file
+    /// A partially annotated training corpus. A sample weight can be specified in the form of
+    /// PATH:WEIGHT as in --tok
+    #[arg(long, group = "dataset", value_parser = parse_weighted_path)]
+    part: Vec<(PathBuf, f64)>,
+
+    /// A word dictionary file. A category of the words can be specified in the form of
+    /// PATH:CATEGORY
+    #[arg(long, value_parser = parse_categorized_path)]
+    dict: Vec<(PathBuf, String)>,
+
+    /// Uses the specified tag (0-origin) of each dictionary word as an additional category. Words
+    /// also remain in the category of their dictionary file, and tag categories never merge with
+    /// file categories of the same name
     #[arg(long)]
-    dict: Vec<PathBuf>,
+    dict_tag_category: Option<usize>,
 
     /// The file to write the trained model to
     #[arg(long)]
@@ -50,6 +66,19 @@ struct Args {
     #[arg(long, default_value = "4")]
     dictn: u8,
 
+    /// A feature template combining characters (c) and character types (t) at positions relative
+    /// to the boundary, e.g., "c-2,c0" (-1 is the left character, and 0 is the right character)
+    #[arg(long)]
+    template: Vec<FeatureTemplate>,
+
+    /// Uses the surfaces of the previous and next tokens as features of tag prediction
+    #[arg(long)]
+    tag_context: bool,
+
+    /// Trains a tag model for out-of-vocabulary tokens to predict the specified tag (0-origin)
+    #[arg(long)]
+    oov_tags: Vec<usize>,
+
     /// The epsilon stopping criterion for classifier training
     #[arg(long, default_value = "0.01")]
     eps: f64,
@@ -62,29 +91,96 @@ struct Args {
     #[arg(long)]
     solver: SolverType,
 
+    /// The number of threads to train tag models
+    #[arg(long, default_value = "1")]
+    threads: usize,
+
     /// Do not normalize training data.
     #[arg(long)]
     no_norm: bool,
 }
 
+fn parse_weighted_path(s: &str) -> Result<(PathBuf, f64), String> {
+    if let Some((path, weight)) = s.rsplit_once(':') {
+        if let Ok(weight) = weight.parse::<f64>() {
+            if !weight.is_finite() || weight < 0. {
+                return Err("weight must be a non-negative finite number".to_string());
+            }
+            return Ok((path.into(), weight));
+        }
+    }
+    Ok((s.into(), 1.))
+}
+
+fn parse_categorized_path(s: &str) -> Result<(PathBuf, String), String> {
+    if let Some((path, category)) = s.rsplit_once(':') {
+        if !category.is_empty() && !category.chars().any(std::path::is_separator) {
+            return Ok((path.into(), category.to_string()));
+        }
+    }
+    Ok((s.into(), String::new()))
+}
+
+struct ProgressPrinter;
+
+impl TrainingObserver for ProgressPrinter {
+    fn notify(&mut self, progress: TrainingProgress) {
+        match progress {
+            TrainingProgress::FeatureExtraction {
+                n_sentences,
+                n_features,
+            } if n_sentences % 10000 == 0 => {
+                eprint!("# of features: {n_features}\r");
+            }
+            TrainingProgress::BoundaryTrainingFinished => {
+                eprintln!("Finish training word boundaries.");
+                // Messages of the solver are too many to be printed for each tag model.
+                liblinear::toggle_liblinear_stdout_output(false);
+            }
+            TrainingProgress::TagTraining {
+                n_trained,
+                n_tokens,
+            } if n_trained == n_tokens => {
+                eprintln!("Tags: {n_trained}/{n_tokens}");
+            }
+            TrainingProgress::TagTraining {
+                n_trained,
+                n_tokens,
+            } => {
+                eprint!("Tags: {n_trained}/{n_tokens}\r");
+            }
+            TrainingProgress::OovTagTraining { n_trained, n_slots } if n_trained == n_slots => {
+                eprintln!("OOV tags: {n_trained}/{n_slots}");
+            }
+            TrainingProgress::OovTagTraining { n_trained, n_slots } => {
+                eprint!("OOV tags: {n_trained}/{n_slots}\r");
+            }
+            _ => {}
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    let corpus_format = args.corpus_format.corpus_format()?;
+    let tokenized_format = args.corpus_format.tokenized.tokenized_format()?;
 
     let fullwidth_filter = KyteaFullwidthFilter;
 
     eprintln!("Loading dataset...");
     let mut train_sents = vec![];
+    let mut weights = vec![];
 
-    for path in args.tok {
+    for (path, weight) in args.tok {
         eprintln!("Loading {path:?} ...");
         let f = File::open(path)?;
         let f = BufReader::new(f);
-        for (i, line) in f.lines().enumerate() {
+        for (i, record) in corpus_format.records(f).enumerate() {
             if i % 10000 == 0 {
                 eprint!("# of sentences: {i}\r");
                 stderr().flush()?;
             }
-            let s = Sentence::from_tokenized(&line?)?;
+            let s = Sentence::from_format(&record?, &corpus_format)?;
             let s = if args.no_norm {
                 s
             } else {
@@ -93,13 +189,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 new_s.boundaries_mut().clone_from_slice(s.boundaries());
                 new_s.reset_tags(s.n_tags());
                 new_s.tags_mut().clone_from_slice(s.tags());
+                // Spaces between words in the CoNLL-U format are not tokens.
+                if s.preserves_whitespace() {
+                    new_s.preserve_whitespace()?;
+                }
                 new_s
             };
             train_sents.push(s);
+            weights.push(weight);
         }
         eprintln!("# of sentences: {}", train_sents.len());
     }
-    for path in args.part {
+    for (path, weight) in args.part {
         eprintln!("Loading {path:?} ...");
         let f = File::open(path)?;
         let f = BufReader::new(f);
@@ -120,22 +221,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 new_s
             };
             train_sents.push(s);
+            weights.push(weight);
         }
         eprintln!("# of sentences: {}", train_sents.len());
     }
 
     let mut tag_dictionary = vec![];
-    let mut dictionary = BTreeSet::new();
-    for path in args.dict {
+    let mut categorized_dictionary: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut n_words = 0;
+    for (path, file_category) in args.dict {
         eprintln!("Loading {path:?} ...");
         let f = File::open(path)?;
         let f = BufReader::new(f);
+        // Categories are namespaced so that a tag never merges with a file category of the same
+        // name.
+        let file_category = if file_category.is_empty() {
+            file_category
+        } else {
+            format!("file:{file_category}")
+        };
         for line in f.lines() {
-            if dictionary.len() % 10000 == 0 {
-                eprint!("# of words: {}\r", dictionary.len());
+            if n_words % 10000 == 0 {
+                eprint!("# of words: {n_words}\r");
                 stderr().flush()?;
             }
-            let s = Sentence::from_tokenized(&line?)?;
+            let s = Sentence::from_tokenized_with_format(&line?, &tokenized_format)?;
             let s = if args.no_norm {
                 s
             } else {
@@ -147,13 +257,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 new_s
             };
             for token in s.iter_tokens() {
-                dictionary.insert(token.surface().to_string());
+                let tag_category = args
+                    .dict_tag_category
+                    .and_then(|i| token.tags().get(i))
+                    .and_then(|tag| tag.as_ref());
+                if let Some(tag_category) = tag_category {
+                    categorized_dictionary
+                        .entry(format!("tag:{tag_category}"))
+                        .or_default()
+                        .insert(token.surface().to_string());
+                }
+                categorized_dictionary
+                    .entry(file_category.clone())
+                    .or_default()
+                    .insert(token.surface().to_string());
+                n_words += 1;
             }
             tag_dictionary.push(s);
         }
-        eprintln!("# of words: {}", dictionary.len());
+        eprintln!("# of words: {n_words}");
     }
-    let dictionary = dictionary.into_iter().collect();
+    let dictionary = categorized_dictionary
+        .remove("")
+        .map_or_else(Vec::new, |words| words.into_iter().collect());
 
     eprintln!("Extracting into features...");
     let mut trainer = Trainer::new(
@@ -165,12 +291,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         args.dictn,
         &tag_dictionary,
     )?;
-    for (i, s) in train_sents.iter().enumerate() {
-        if i % 10000 == 0 {
-            eprint!("# of features: {}\r", trainer.n_features(),);
-            stderr().flush()?;
-        }
-        trainer.add_example(s);
+    for (category, words) in categorized_dictionary {
+        trainer.add_dictionary_words(&category, words)?;
+    }
+    trainer.set_feature_templates(args.template)?;
+    trainer.set_tag_context(args.tag_context)?;
+    trainer.set_oov_tag_slots(args.oov_tags)?;
+    trainer.set_n_threads(args.threads)?;
+    trainer.set_observer(ProgressPrinter);
+    for (s, &weight) in train_sents.iter().zip(&weights) {
+        trainer.add_weighted_example(s, weight)?;
     }
     eprintln!("# of features: {}", trainer.n_features(),);
 
@@ -26,7 +26,7 @@ use boundary_scorer::CharScorerBoundary;
 use boundary_tag_scorer::CharScorerBoundaryTag;
 
 #[derive(Default)]
-struct CharWeightMerger<W> {
+pub(crate) struct CharWeightMerger<W> {
     map: BTreeMap<String, RefCell<(W, bool)>>,
 }
 
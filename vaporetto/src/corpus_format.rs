@@ -0,0 +1,171 @@
+#[cfg(feature = "std")]
+use alloc::string::String;
+
+#[cfg(feature = "std")]
+use std::io::{BufRead, Lines};
+
+use crate::errors::Result;
+use crate::sentence::{Sentence, TokenizedFormat};
+
+/// Format of a corpus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CorpusFormat {
+    /// The tokenized format with the given separators, where each line is a sentence.
+    /// See [`Sentence::from_tokenized_with_format()`].
+    Tokenized(TokenizedFormat),
+
+    /// The CoNLL-U format, where sentences are separated by empty lines.
+    /// See [`Sentence::from_conllu()`].
+    Conllu,
+
+    /// The TSV format, where sentences are separated by empty lines.
+    /// See [`Sentence::from_tsv()`].
+    Tsv,
+}
+
+impl Default for CorpusFormat {
+    /// Creates the tokenized format with the default separators.
+    fn default() -> Self {
+        Self::Tokenized(TokenizedFormat::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl CorpusFormat {
+    /// Returns an iterator over records of a corpus, each of which is a sentence.
+    ///
+    /// A record is a line in the tokenized format, and consecutive non-empty lines in the CoNLL-U
+    /// and TSV formats. Each line of a multi-line record ends with a line feed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CorpusFormat, Sentence};
+    ///
+    /// let corpus = "火星\t名詞\n猫\t名詞\n\n\nだ\n";
+    /// let sentences: Vec<_> = CorpusFormat::Tsv
+    ///     .records(corpus.as_bytes())
+    ///     .map(|record| Sentence::from_format(&record.unwrap(), &CorpusFormat::Tsv).unwrap())
+    ///     .collect();
+    /// assert_eq!(2, sentences.len());
+    /// assert_eq!("火星猫", sentences[0].as_raw_text());
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn records<R>(self, rdr: R) -> Records<R>
+    where
+        R: BufRead,
+    {
+        Records {
+            lines: rdr.lines(),
+            multiline: !matches!(self, Self::Tokenized(_)),
+        }
+    }
+}
+
+/// Iterator over records of a corpus returned by [`CorpusFormat::records()`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct Records<R> {
+    lines: Lines<R>,
+    multiline: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R> Iterator for Records<R>
+where
+    R: BufRead,
+{
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.multiline {
+            return self.lines.next().map(|line| Ok(line?));
+        }
+        let mut record = String::new();
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.is_empty() {
+                if !record.is_empty() {
+                    return Some(Ok(record));
+                }
+            } else {
+                record.push_str(&line);
+                record.push('\n');
+            }
+        }
+        (!record.is_empty()).then_some(Ok(record))
+    }
+}
+
+impl<'a, 'b> Sentence<'a, 'b> {
+    /// Creates a new [`Sentence`] from a record of a corpus in the given format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error variant when the record is invalid in the format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CorpusFormat, Sentence, TokenizedFormat};
+    ///
+    /// let format = CorpusFormat::Tokenized(TokenizedFormat::new('|', '_', '\\').unwrap());
+    /// let s = Sentence::from_format("火星_名詞|猫_名詞", &format).unwrap();
+    ///
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星/名詞 猫/名詞", buf);
+    /// ```
+    pub fn from_format(record: &str, format: &CorpusFormat) -> Result<Self> {
+        match format {
+            CorpusFormat::Tokenized(format) => Self::from_tokenized_with_format(record, format),
+            CorpusFormat::Conllu => Self::from_conllu(record),
+            CorpusFormat::Tsv => Self::from_tsv(record),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    fn records(format: CorpusFormat, corpus: &str) -> Vec<String> {
+        format
+            .records(corpus.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_records_tokenized() {
+        assert_eq!(
+            vec!["火星 猫", "", "だ"],
+            records(CorpusFormat::default(), "火星 猫\n\nだ"),
+        );
+    }
+
+    #[test]
+    fn test_records_multiline() {
+        assert_eq!(
+            vec!["火星\n猫\n", "だ\n"],
+            records(CorpusFormat::Tsv, "\n火星\n猫\n\n\nだ"),
+        );
+        assert!(records(CorpusFormat::Conllu, "\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_from_format() {
+        let s = Sentence::from_format(
+            "1\t火星\t_\tNOUN\t_\t_\t_\t_\t_\t_\n",
+            &CorpusFormat::Conllu,
+        )
+        .unwrap();
+        assert_eq!("火星", s.as_raw_text());
+        assert!(Sentence::from_format("火星\t名詞", &CorpusFormat::Conllu).is_err());
+    }
+}
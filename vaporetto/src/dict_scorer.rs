@@ -0,0 +1,242 @@
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock};
+
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use bincode::{
+    de::BorrowDecoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    BorrowDecode, Decode, Encode,
+};
+#[cfg(feature = "charwise-pma")]
+use daachorse::charwise::CharwiseDoubleArrayAhoCorasick;
+#[cfg(not(feature = "charwise-pma"))]
+use daachorse::DoubleArrayAhoCorasick;
+
+use crate::char_scorer::CharWeightMerger;
+use crate::dict_model::DictModel;
+use crate::errors::{Result, VaporettoError};
+use crate::predictor::{PositionalWeight, WeightVector};
+use crate::sentence::Sentence;
+
+/// Scorer of dictionary words.
+///
+/// Dictionary words are scored separately from character n-grams so that the dictionary can be
+/// replaced without rebuilding other scorers.
+pub struct DictScorer {
+    #[cfg(not(feature = "charwise-pma"))]
+    pma: DoubleArrayAhoCorasick<u32>,
+    #[cfg(feature = "charwise-pma")]
+    pma: CharwiseDoubleArrayAhoCorasick<u32>,
+    weights: Vec<PositionalWeight<WeightVector>>,
+}
+
+impl<'de> BorrowDecode<'de> for DictScorer {
+    /// WARNING: This function is inherently unsafe. Do not publish this function outside this
+    /// crate.
+    fn borrow_decode<D: BorrowDecoder<'de>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let pma_data: &[u8] = BorrowDecode::borrow_decode(decoder)?;
+        #[cfg(not(feature = "charwise-pma"))]
+        let (pma, _) = unsafe { DoubleArrayAhoCorasick::deserialize_unchecked(pma_data) };
+        #[cfg(feature = "charwise-pma")]
+        let (pma, _) = unsafe { CharwiseDoubleArrayAhoCorasick::deserialize_unchecked(pma_data) };
+        Ok(Self {
+            pma,
+            weights: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for DictScorer {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        let pma_data = self.pma.serialize();
+        Encode::encode(&pma_data, encoder)?;
+        Encode::encode(&self.weights, encoder)?;
+        Ok(())
+    }
+}
+
+impl DictScorer {
+    pub fn new(dict_model: DictModel) -> Result<Option<Self>> {
+        if dict_model.0.is_empty() {
+            return Ok(None);
+        }
+        let mut merger = CharWeightMerger::default();
+        for d in dict_model.0 {
+            let word_len = d.word.chars().count();
+            let word_len = i16::try_from(word_len).map_err(|_| {
+                VaporettoError::invalid_model(
+                    "words must be shorter than or equal to 32767 characters",
+                )
+            })?;
+            let weight = PositionalWeight::new(-word_len, d.weights);
+            merger.add(d.word, weight);
+        }
+        let mut words = vec![];
+        let mut weights = vec![];
+        for (word, weight) in merger.merge() {
+            words.push(word);
+            weights.push(weight.into());
+        }
+        #[cfg(not(feature = "charwise-pma"))]
+        let pma = DoubleArrayAhoCorasick::new(words)
+            .map_err(|_| VaporettoError::invalid_model("failed to build the automaton"))?;
+        #[cfg(feature = "charwise-pma")]
+        let pma = CharwiseDoubleArrayAhoCorasick::new(words)
+            .map_err(|_| VaporettoError::invalid_model("failed to build the automaton"))?;
+        Ok(Some(Self { pma, weights }))
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    #[inline(always)]
+    pub fn add_scores(&self, sentence: &mut Sentence) {
+        #[cfg(not(feature = "charwise-pma"))]
+        let it = self
+            .pma
+            .find_overlapping_no_suffix_iter(sentence.text.as_bytes());
+        #[cfg(feature = "charwise-pma")]
+        let it = self.pma.find_overlapping_no_suffix_iter(&sentence.text);
+        for m in it {
+            debug_assert!(m.end() != 0 && sentence.text.is_char_boundary(m.end()));
+            let end = unsafe { sentence.str_to_char_pos(m.end()) };
+            debug_assert!(usize::try_from(m.value()).unwrap() < self.weights.len());
+            let weight = unsafe {
+                self.weights
+                    .get_unchecked(usize::try_from(m.value()).unwrap())
+            };
+            weight.add_score(
+                (end + sentence.score_padding - 1) as isize,
+                &mut sentence.boundary_scores,
+            );
+        }
+    }
+}
+
+/// Scorer of dictionary words that can be replaced after the predictor is created.
+///
+/// With the `std` feature, the scorer is replaced behind a lock, so a predictor shared across
+/// threads can replace it while others are predicting. Each prediction holds the scorer it started
+/// with.
+#[derive(Default)]
+pub struct SwappableDictScorer {
+    #[cfg(feature = "std")]
+    scorer: RwLock<Option<Arc<DictScorer>>>,
+    #[cfg(not(feature = "std"))]
+    scorer: Option<DictScorer>,
+}
+
+impl<'de> BorrowDecode<'de> for SwappableDictScorer {
+    /// WARNING: This function is inherently unsafe. Do not publish this function outside this
+    /// crate.
+    fn borrow_decode<D: BorrowDecoder<'de>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let scorer: Option<DictScorer> = BorrowDecode::borrow_decode(decoder)?;
+        Ok(Self::new(scorer))
+    }
+}
+
+impl Encode for SwappableDictScorer {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        #[cfg(feature = "std")]
+        let scorer = self.get();
+        #[cfg(feature = "std")]
+        let scorer = scorer.as_deref();
+        #[cfg(not(feature = "std"))]
+        let scorer = self.scorer.as_ref();
+        Encode::encode(&scorer, encoder)
+    }
+}
+
+impl SwappableDictScorer {
+    pub fn new(scorer: Option<DictScorer>) -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            scorer: RwLock::new(scorer.map(Arc::new)),
+            #[cfg(not(feature = "std"))]
+            scorer,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn get(&self) -> Option<Arc<DictScorer>> {
+        // The lock is never poisoned because no code panics while holding it.
+        self.scorer
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn replace(&self, scorer: Option<DictScorer>) {
+        let scorer = scorer.map(Arc::new);
+        *self.scorer.write().unwrap_or_else(PoisonError::into_inner) = scorer;
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn replace(&mut self, scorer: Option<DictScorer>) {
+        self.scorer = scorer;
+    }
+
+    #[inline(always)]
+    pub fn add_scores(&self, sentence: &mut Sentence) {
+        #[cfg(feature = "std")]
+        if let Some(scorer) = self.get() {
+            scorer.add_scores(sentence);
+        }
+        #[cfg(not(feature = "std"))]
+        if let Some(scorer) = self.scorer.as_ref() {
+            scorer.add_scores(sentence);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dict_model::WordWeightRecord;
+    use crate::predictor::WEIGHT_FIXED_LEN;
+
+    #[test]
+    fn test_add_scores() {
+        // input:  我  ら  は  全  世  界  の  国  民
+        // dict:
+        //   全世界:         26  27  28  29
+        //   世界:               30  31  32
+        //   世:                 33  34
+        let scorer = DictScorer::new(DictModel(vec![
+            WordWeightRecord {
+                word: "全世界".into(),
+                weights: vec![26, 27, 28, 29],
+                comment: "".into(),
+            },
+            WordWeightRecord {
+                word: "世界".into(),
+                weights: vec![30, 31, 32],
+                comment: "".into(),
+            },
+            WordWeightRecord {
+                word: "世".into(),
+                weights: vec![33, 34],
+                comment: "".into(),
+            },
+        ]))
+        .unwrap()
+        .unwrap();
+        let mut sentence = Sentence::from_raw("我らは全世界の国民").unwrap();
+        sentence.score_padding = WEIGHT_FIXED_LEN - 1;
+        sentence.boundary_scores.clear();
+        sentence
+            .boundary_scores
+            .resize(sentence.score_padding * 2 + sentence.len() - 1, 1);
+        scorer.add_scores(&mut sentence);
+        assert_eq!(&[1, 1, 27, 91, 94, 62, 1, 1], sentence.boundary_scores(),);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(DictScorer::new(DictModel(vec![])).unwrap().is_none());
+    }
+}
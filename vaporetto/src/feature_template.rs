@@ -0,0 +1,205 @@
+use core::fmt;
+use core::str::FromStr;
+
+use alloc::vec::Vec;
+
+use bincode::{Decode, Encode};
+
+use crate::errors::{Result, VaporettoError};
+use crate::sentence::Sentence;
+
+// Values used in keys for positions outside the sentence. 0xFF never appears in UTF-8 strings,
+// and 0 is not a valid character type.
+const OUT_OF_RANGE_CHAR: u8 = 0xFF;
+const OUT_OF_RANGE_TYPE: u8 = 0;
+
+/// An element of a [`FeatureTemplate`].
+///
+/// The position is relative to the character boundary: `-1` indicates the character on the left
+/// side of the boundary, and `0` indicates the character on the right side.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Decode, Encode)]
+pub enum TemplateElement {
+    /// A character at the relative position.
+    Char(i8),
+
+    /// A character type at the relative position.
+    CharType(i8),
+}
+
+/// A feature template that combines characters and character types at arbitrary positions.
+///
+/// Feature templates extend the fixed n-gram features of the [`Trainer`](crate::Trainer).
+/// The templates are stored in the model, so the predictor generates the same features.
+///
+/// A template can be parsed from comma-separated elements, where `c` and `t` followed by a
+/// relative position indicate a character and a character type, respectively.
+/// Positions outside the sentence are represented by a special value, so templates can also
+/// express features such as "the first character of the line".
+///
+/// # Examples
+///
+/// ```
+/// use vaporetto::{FeatureTemplate, TemplateElement};
+///
+/// // A skip-gram of the characters on both sides of the boundary.
+/// let template: FeatureTemplate = "c-2,c0".parse().unwrap();
+/// assert_eq!(
+///     &[TemplateElement::Char(-2), TemplateElement::Char(0)],
+///     template.elements(),
+/// );
+///
+/// // A combination of a character type and a character.
+/// let template: FeatureTemplate = "t-1,c0".parse().unwrap();
+/// assert_eq!("t-1,c0", template.to_string());
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Decode, Encode)]
+pub struct FeatureTemplate(Vec<TemplateElement>);
+
+impl FeatureTemplate {
+    /// Creates a new feature template.
+    ///
+    /// # Errors
+    ///
+    /// If `elements` is empty, an error variant will be returned.
+    pub fn new(elements: Vec<TemplateElement>) -> Result<Self> {
+        if elements.is_empty() {
+            return Err(VaporettoError::invalid_argument(
+                "elements",
+                "must contain at least one element",
+            ));
+        }
+        Ok(Self(elements))
+    }
+
+    /// Returns the elements of this template.
+    pub fn elements(&self) -> &[TemplateElement] {
+        &self.0
+    }
+
+    /// Appends the key of the feature at the given boundary.
+    ///
+    /// The key starts with the template ID, followed by the values of the elements. Since the
+    /// length of each value is determined by its first byte, keys never collide.
+    pub(crate) fn push_key(&self, id: u8, sentence: &Sentence, boundary: usize, key: &mut Vec<u8>) {
+        key.push(id);
+        for &element in &self.0 {
+            let (TemplateElement::Char(offset) | TemplateElement::CharType(offset)) = element;
+            let pos = isize::try_from(boundary + 1).unwrap() + isize::from(offset);
+            let pos = usize::try_from(pos)
+                .ok()
+                .filter(|&pos| pos < sentence.len());
+            match (element, pos) {
+                (TemplateElement::Char(_), Some(pos)) => {
+                    key.extend_from_slice(sentence.text_substring(pos, pos + 1).as_bytes());
+                }
+                (TemplateElement::Char(_), None) => key.push(OUT_OF_RANGE_CHAR),
+                (TemplateElement::CharType(_), Some(pos)) => key.push(sentence.char_types()[pos]),
+                (TemplateElement::CharType(_), None) => key.push(OUT_OF_RANGE_TYPE),
+            }
+        }
+    }
+}
+
+impl FromStr for FeatureTemplate {
+    type Err = VaporettoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut elements = vec![];
+        for element in s.split(',') {
+            let element = element.trim();
+            let (kind, offset) = element.split_at(element.len().min(1));
+            let offset = offset.parse().map_err(|_| {
+                VaporettoError::invalid_argument("s", format!("invalid position: {element}"))
+            })?;
+            elements.push(match kind {
+                "c" => TemplateElement::Char(offset),
+                "t" => TemplateElement::CharType(offset),
+                _ => {
+                    return Err(VaporettoError::invalid_argument(
+                        "s",
+                        format!("invalid element: {element}"),
+                    ))
+                }
+            });
+        }
+        Self::new(elements)
+    }
+}
+
+impl fmt::Display for FeatureTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, element) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            match element {
+                TemplateElement::Char(offset) => write!(f, "c{offset}")?,
+                TemplateElement::CharType(offset) => write!(f, "t{offset}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Decode, Encode)]
+pub struct TemplateWeight {
+    pub(crate) key: Vec<u8>,
+    pub(crate) weight: i32,
+}
+
+#[derive(Clone, Debug, Default, Decode, Encode)]
+pub struct TemplateModel {
+    pub(crate) templates: Vec<FeatureTemplate>,
+    pub(crate) weights: Vec<TemplateWeight>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    use crate::sentence::CharacterType::*;
+
+    #[test]
+    fn test_parse_template() {
+        let template: FeatureTemplate = "c-1, t0,c+2".parse().unwrap();
+        assert_eq!(
+            &[
+                TemplateElement::Char(-1),
+                TemplateElement::CharType(0),
+                TemplateElement::Char(2),
+            ],
+            template.elements(),
+        );
+        assert_eq!("c-1,t0,c2", template.to_string());
+    }
+
+    #[test]
+    fn test_parse_invalid_template() {
+        assert!("".parse::<FeatureTemplate>().is_err());
+        assert!("c".parse::<FeatureTemplate>().is_err());
+        assert!("x1".parse::<FeatureTemplate>().is_err());
+        assert!("c1,,t2".parse::<FeatureTemplate>().is_err());
+        assert!("c1000".parse::<FeatureTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_push_key() {
+        let s = Sentence::from_raw("火星A").unwrap();
+        let template: FeatureTemplate = "c-2,t0,c1".parse().unwrap();
+        let mut key = vec![];
+
+        template.push_key(3, &s, 0, &mut key);
+        let mut expected = vec![3, OUT_OF_RANGE_CHAR, Kanji as u8];
+        expected.extend_from_slice("A".as_bytes());
+        assert_eq!(expected, key);
+
+        key.clear();
+        template.push_key(3, &s, 1, &mut key);
+        let mut expected = vec![3];
+        expected.extend_from_slice("火".as_bytes());
+        expected.extend_from_slice(&[Roman as u8, OUT_OF_RANGE_CHAR]);
+        assert_eq!(expected, key);
+    }
+}
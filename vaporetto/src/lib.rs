@@ -61,12 +61,19 @@ compile_error!("`alloc` feature is currently required to build this crate");
 extern crate alloc;
 
 mod char_scorer;
+mod corpus_format;
 mod dict_model;
+mod dict_scorer;
+mod feature_template;
 mod model;
 mod ngram_model;
+mod oov_tag_model;
 mod predictor;
 mod sentence;
+mod sentence_diff;
+mod template_scorer;
 mod type_scorer;
+mod user_dictionary;
 mod utils;
 
 pub mod errors;
@@ -79,13 +86,25 @@ mod trainer;
 #[cfg(feature = "kytea")]
 mod kytea_model;
 
+pub use corpus_format::CorpusFormat;
 pub use dict_model::WordWeightRecord;
+pub use feature_template::{FeatureTemplate, TemplateElement};
 pub use model::Model;
-pub use predictor::Predictor;
-pub use sentence::{CharacterBoundary, CharacterType, Sentence, Token, TokenIterator};
+pub use predictor::{Predictor, TagPrediction};
+pub use sentence::{
+    CharacterBoundary, CharacterType, Sentence, Token, TokenIterator, TokenizedFormat,
+};
+pub use sentence_diff::{DiffKind, TokenDiff};
+pub use user_dictionary::{ConflictResolution, UserDictionary, UserWord};
+
+#[cfg(feature = "std")]
+pub use corpus_format::Records;
+
+#[cfg(feature = "tag-prediction")]
+pub use predictor::TagCandidate;
 
 #[cfg(feature = "train")]
-pub use trainer::{SolverType, Trainer};
+pub use trainer::{SolverType, Trainer, TrainingObserver, TrainingProgress};
 
 #[cfg(feature = "kytea")]
 pub use kytea_model::KyteaModel;
@@ -5,14 +5,20 @@ use alloc::vec::Vec;
 use std::io::{Read, Write};
 
 use bincode::{Decode, Encode};
+use hashbrown::HashMap;
 
 use crate::dict_model::{DictModel, WordWeightRecord};
 use crate::errors::{Result, VaporettoError};
+use crate::feature_template::{FeatureTemplate, TemplateModel};
 use crate::ngram_model::{NgramModel, TagNgramModel};
+use crate::oov_tag_model::OovTagModel;
 use crate::utils::VecWriter;
 
 /// Magic number.
-const MODEL_MAGIC: &[u8] = b"VaporettoTokenizer 0.5.0\n";
+const MODEL_MAGIC: &[u8] = b"VaporettoTokenizer 0.7.0\n";
+
+/// Magic number of models created by Vaporetto 0.5 and 0.6, which can still be read.
+const MODEL_MAGIC_0_5: &[u8] = b"VaporettoTokenizer 0.5.0\n";
 
 // For each token, a model is trained for every tag independently, but the scores of all tags are
 // calculated in parallel during prediction.
@@ -21,6 +27,8 @@ const MODEL_MAGIC: &[u8] = b"VaporettoTokenizer 0.5.0\n";
 // For example, the following token has 3 POS tags and 3 pronunciation tags, so the score array
 // contains 6 items. The predictor picks the tag with the largest score.
 //
+// Tags are stored as IDs in the tag vocabulary shared by all tokens.
+//
 //   token:   "君"
 //   tags:    [["名詞", "代名詞", "接尾辞"], ["クン", "キミ", "ギミ"]]
 //   scores:  [    176,     3647,       39,      518,   9346,    126 ]
@@ -40,10 +48,58 @@ const MODEL_MAGIC: &[u8] = b"VaporettoTokenizer 0.5.0\n";
 #[derive(Debug, Decode, Encode)]
 pub struct TagModel {
     pub(crate) token: String,
-    pub(crate) tags: Vec<Vec<String>>,
+    pub(crate) tags: Vec<Vec<u32>>,
     pub(crate) char_ngram_model: TagNgramModel<String>,
     pub(crate) type_ngram_model: TagNgramModel<Vec<u8>>,
     pub(crate) bias: Vec<i32>,
+    pub(crate) prev_token_weights: Vec<TagContextWeight>,
+    pub(crate) next_token_weights: Vec<TagContextWeight>,
+    // Multipliers to restore raw scores from quantized scores of tags with two or more
+    // candidates. This is empty if unknown.
+    pub(crate) quantize_multipliers: Vec<f64>,
+}
+
+/// Weights of a tag model associated with the surface of a neighboring token.
+///
+/// An empty surface indicates the beginning or the end of the sentence.
+#[derive(Debug, Decode, Encode)]
+pub struct TagContextWeight {
+    pub(crate) token: String,
+    pub(crate) weights: Vec<i32>,
+}
+
+// Tag model data of the format 0.5.0.
+#[derive(Decode)]
+struct TagModel0_5 {
+    token: String,
+    tags: Vec<Vec<String>>,
+    char_ngram_model: TagNgramModel<String>,
+    type_ngram_model: TagNgramModel<Vec<u8>>,
+    bias: Vec<i32>,
+}
+
+impl TagModel0_5 {
+    fn into_tag_model(self, vocabulary: &mut TagVocabularyBuilder) -> TagModel {
+        TagModel {
+            token: self.token,
+            tags: self
+                .tags
+                .into_iter()
+                .map(|cands| {
+                    cands
+                        .into_iter()
+                        .map(|tag| vocabulary.intern(&tag))
+                        .collect()
+                })
+                .collect(),
+            char_ngram_model: self.char_ngram_model,
+            type_ngram_model: self.type_ngram_model,
+            bias: self.bias,
+            prev_token_weights: vec![],
+            next_token_weights: vec![],
+            quantize_multipliers: vec![],
+        }
+    }
 }
 
 impl TagModel {
@@ -51,6 +107,42 @@ impl TagModel {
     pub fn token(&self) -> &str {
         &self.token
     }
+
+    /// Returns IDs of tag candidates of each tag in the tag vocabulary.
+    pub fn tag_ids(&self) -> &[Vec<u32>] {
+        &self.tags
+    }
+}
+
+/// Builder of a tag vocabulary that assigns IDs to tags in order of appearance.
+#[derive(Default)]
+pub struct TagVocabularyBuilder {
+    ids: HashMap<String, u32>,
+    tags: Vec<String>,
+}
+
+impl TagVocabularyBuilder {
+    /// Returns the ID of the given tag, adding it to the vocabulary if it is new.
+    pub fn intern(&mut self, tag: &str) -> u32 {
+        if let Some(&id) = self.ids.get(tag) {
+            return id;
+        }
+        let id = u32::try_from(self.tags.len()).unwrap();
+        self.ids.insert(tag.into(), id);
+        self.tags.push(tag.into());
+        id
+    }
+
+    /// Returns the ID of the given tag if it exists.
+    #[cfg(feature = "train")]
+    pub fn get(&self, tag: &str) -> Option<u32> {
+        self.ids.get(tag).copied()
+    }
+
+    /// Returns the tag strings indexed by IDs.
+    pub fn build(self) -> Vec<String> {
+        self.tags
+    }
 }
 
 /// Model data.
@@ -67,6 +159,48 @@ pub struct ModelData {
     pub(crate) type_window_size: u8,
     // Instead of using Map, we use Vec to increase compression ratio and performance.
     pub(crate) tag_models: Vec<TagModel>,
+    pub(crate) template_model: TemplateModel,
+    pub(crate) oov_tag_model: OovTagModel,
+    // Tag strings indexed by IDs stored in tag models.
+    pub(crate) tag_vocabulary: Vec<String>,
+    // Multiplier to restore raw scores from quantized boundary scores. This is 0 if unknown.
+    pub(crate) quantize_multiplier: f64,
+}
+
+// Model data of the format 0.5.0.
+#[derive(Decode)]
+struct ModelData0_5 {
+    char_ngram_model: NgramModel<String>,
+    type_ngram_model: NgramModel<Vec<u8>>,
+    dict_model: DictModel,
+    bias: i32,
+    char_window_size: u8,
+    type_window_size: u8,
+    tag_models: Vec<TagModel0_5>,
+}
+
+impl From<ModelData0_5> for ModelData {
+    fn from(data: ModelData0_5) -> Self {
+        let mut vocabulary = TagVocabularyBuilder::default();
+        let tag_models = data
+            .tag_models
+            .into_iter()
+            .map(|model| model.into_tag_model(&mut vocabulary))
+            .collect();
+        Self {
+            char_ngram_model: data.char_ngram_model,
+            type_ngram_model: data.type_ngram_model,
+            dict_model: data.dict_model,
+            bias: data.bias,
+            char_window_size: data.char_window_size,
+            type_window_size: data.type_window_size,
+            tag_models,
+            template_model: TemplateModel::default(),
+            oov_tag_model: OovTagModel::default(),
+            tag_vocabulary: vocabulary.build(),
+            quantize_multiplier: 0.,
+        }
+    }
 }
 
 impl Model {
@@ -88,6 +222,18 @@ impl Model {
             char_window_size,
             type_window_size,
             tag_models,
+            template_model: TemplateModel {
+                templates: Vec::new(),
+                weights: Vec::new(),
+            },
+            oov_tag_model: OovTagModel {
+                tags: Vec::new(),
+                bias: Vec::new(),
+                weights: Vec::new(),
+                quantize_multipliers: Vec::new(),
+            },
+            tag_vocabulary: Vec::new(),
+            quantize_multiplier: 0.,
         })
     }
 
@@ -125,12 +271,21 @@ impl Model {
     ///
     /// When bincode generates an error, it will be returned as is.
     pub fn read_slice(slice: &[u8]) -> Result<(Self, &[u8])> {
-        if &slice[..MODEL_MAGIC.len()] != MODEL_MAGIC {
-            return Err(VaporettoError::invalid_model("model version mismatch"));
-        }
         let config = bincode::config::standard();
-        let (data, size) = bincode::decode_from_slice(&slice[MODEL_MAGIC.len()..], config)?;
-        Ok((Self(data), &slice[MODEL_MAGIC.len() + size..]))
+        match slice.get(..MODEL_MAGIC.len()) {
+            Some(MODEL_MAGIC) => {
+                let (data, size) = bincode::decode_from_slice(&slice[MODEL_MAGIC.len()..], config)?;
+                Ok((Self(data), &slice[MODEL_MAGIC.len() + size..]))
+            }
+            Some(MODEL_MAGIC_0_5) => {
+                let (data, size) = bincode::decode_from_slice::<ModelData0_5, _>(
+                    &slice[MODEL_MAGIC_0_5.len()..],
+                    config,
+                )?;
+                Ok((Self(data.into()), &slice[MODEL_MAGIC_0_5.len() + size..]))
+            }
+            _ => Err(VaporettoError::invalid_model("model version mismatch")),
+        }
     }
 
     /// Creates a model from a reader.
@@ -145,11 +300,14 @@ impl Model {
     {
         let mut magic = [0; MODEL_MAGIC.len()];
         rdr.read_exact(&mut magic)?;
-        if magic != MODEL_MAGIC {
-            return Err(VaporettoError::invalid_model("model version mismatch"));
-        }
         let config = bincode::config::standard();
-        Ok(Self(bincode::decode_from_std_read(&mut rdr, config)?))
+        match magic.as_slice() {
+            MODEL_MAGIC => Ok(Self(bincode::decode_from_std_read(&mut rdr, config)?)),
+            MODEL_MAGIC_0_5 => Ok(Self(
+                bincode::decode_from_std_read::<ModelData0_5, _, _>(&mut rdr, config)?.into(),
+            )),
+            _ => Err(VaporettoError::invalid_model("model version mismatch")),
+        }
     }
 
     /// Returns the slice of dictionary words.
@@ -166,4 +324,14 @@ impl Model {
     pub fn tag_models(&self) -> &[TagModel] {
         &self.0.tag_models
     }
+
+    /// Returns the tag vocabulary. Tags in tag models are stored as indices of this slice.
+    pub fn tag_vocabulary(&self) -> &[String] {
+        &self.0.tag_vocabulary
+    }
+
+    /// Returns the slice of feature templates.
+    pub fn feature_templates(&self) -> &[FeatureTemplate] {
+        &self.0.template_model.templates
+    }
 }
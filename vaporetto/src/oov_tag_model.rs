@@ -0,0 +1,157 @@
+use alloc::vec::Vec;
+
+use bincode::{Decode, Encode};
+
+// The maximum length of prefixes and suffixes in characters.
+const AFFIX_MAX_LEN: usize = 3;
+// The maximum number of character type runs.
+const TYPE_RUNS_MAX_LEN: usize = 4;
+// Tokens longer than this value are grouped together.
+const TOKEN_MAX_LEN: usize = 8;
+
+const FEATURE_PREFIX: u8 = 0;
+const FEATURE_SUFFIX: u8 = 1;
+const FEATURE_TYPE_RUNS: u8 = 2;
+const FEATURE_LENGTH: u8 = 3;
+
+/// Calls `f` with the key of each feature of a token used by the OOV tag model.
+///
+/// Features are prefixes and suffixes of the token, runs of character types (e.g., "KH" for
+/// "見る"), and the length of the token. Each key starts with the feature kind.
+pub fn for_each_oov_feature<F>(surface: &str, char_types: &[u8], mut f: F)
+where
+    F: FnMut(&[u8]),
+{
+    let mut key = vec![];
+    let n_chars = char_types.len();
+    let mut char_ends = surface.char_indices().map(|(i, _)| i).skip(1);
+    for n in 1..=n_chars.min(AFFIX_MAX_LEN) {
+        let end = char_ends.next().unwrap_or(surface.len());
+        key.clear();
+        key.push(FEATURE_PREFIX);
+        key.extend_from_slice(&surface.as_bytes()[..end]);
+        f(&key);
+        let (start, _) = surface.char_indices().rev().nth(n - 1).unwrap();
+        key.clear();
+        key.push(FEATURE_SUFFIX);
+        key.extend_from_slice(&surface.as_bytes()[start..]);
+        f(&key);
+    }
+    key.clear();
+    key.push(FEATURE_TYPE_RUNS);
+    let mut prev_type = None;
+    for &t in char_types {
+        if prev_type != Some(t) {
+            if key.len() > TYPE_RUNS_MAX_LEN {
+                break;
+            }
+            key.push(t);
+            prev_type = Some(t);
+        }
+    }
+    f(&key);
+    key.clear();
+    key.push(FEATURE_LENGTH);
+    key.push(u8::try_from(n_chars.min(TOKEN_MAX_LEN)).unwrap());
+    f(&key);
+}
+
+/// Weights of an OOV tag feature.
+#[derive(Clone, Debug, Decode, Encode)]
+pub struct OovTagWeight {
+    pub(crate) key: Vec<u8>,
+    pub(crate) weights: Vec<i32>,
+}
+
+// Like the tag model of each token, the score array is a concatenation of all classes of all tags
+// that have two or more candidates. Tags without candidates are not predicted.
+/// Internal representation of a tag model for out-of-vocabulary tokens.
+#[derive(Clone, Debug, Default, Decode, Encode)]
+pub struct OovTagModel {
+    pub(crate) tags: Vec<Vec<u32>>,
+    pub(crate) bias: Vec<i32>,
+    pub(crate) weights: Vec<OovTagWeight>,
+    pub(crate) quantize_multipliers: Vec<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::sentence::CharacterType::*;
+
+    fn collect_features(surface: &str, char_types: &[u8]) -> Vec<Vec<u8>> {
+        let mut features = vec![];
+        for_each_oov_feature(surface, char_types, |key| features.push(key.to_vec()));
+        features
+    }
+
+    fn key(kind: u8, s: &str) -> Vec<u8> {
+        let mut key = vec![kind];
+        key.extend_from_slice(s.as_bytes());
+        key
+    }
+
+    #[test]
+    fn test_oov_features() {
+        let features = collect_features(
+            "見積もる",
+            &[Kanji as u8, Kanji as u8, Hiragana as u8, Hiragana as u8],
+        );
+        assert_eq!(
+            vec![
+                key(FEATURE_PREFIX, "見"),
+                key(FEATURE_SUFFIX, "る"),
+                key(FEATURE_PREFIX, "見積"),
+                key(FEATURE_SUFFIX, "もる"),
+                key(FEATURE_PREFIX, "見積も"),
+                key(FEATURE_SUFFIX, "積もる"),
+                vec![FEATURE_TYPE_RUNS, Kanji as u8, Hiragana as u8],
+                vec![FEATURE_LENGTH, 4],
+            ],
+            features,
+        );
+    }
+
+    #[test]
+    fn test_oov_features_short() {
+        let features = collect_features("A", &[Roman as u8]);
+        assert_eq!(
+            vec![
+                key(FEATURE_PREFIX, "A"),
+                key(FEATURE_SUFFIX, "A"),
+                vec![FEATURE_TYPE_RUNS, Roman as u8],
+                vec![FEATURE_LENGTH, 1],
+            ],
+            features,
+        );
+    }
+
+    #[test]
+    fn test_oov_features_long() {
+        let features = collect_features(
+            "ABC1あア漢字",
+            &[
+                Roman as u8,
+                Roman as u8,
+                Roman as u8,
+                Digit as u8,
+                Hiragana as u8,
+                Katakana as u8,
+                Kanji as u8,
+                Kanji as u8,
+            ],
+        );
+        assert_eq!(
+            vec![
+                FEATURE_TYPE_RUNS,
+                Roman as u8,
+                Digit as u8,
+                Hiragana as u8,
+                Katakana as u8
+            ],
+            features[6],
+        );
+        assert_eq!(vec![FEATURE_LENGTH, 8], features[7]);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "tag-prediction")]
+use core::cmp::Reverse;
 use core::ops::AddAssign;
 
 #[cfg(all(feature = "fix-weight-length", feature = "portable-simd"))]
@@ -7,7 +9,6 @@ use alloc::vec::Vec;
 
 #[cfg(feature = "tag-prediction")]
 use alloc::borrow::Cow;
-#[cfg(feature = "tag-prediction")]
 use alloc::string::String;
 
 use bincode::{
@@ -21,10 +22,18 @@ use bincode::{
 use hashbrown::HashMap;
 
 use crate::char_scorer::CharScorer;
-use crate::errors::Result;
+use crate::dict_model::{DictModel, WordWeightRecord};
+use crate::dict_scorer::{DictScorer, SwappableDictScorer};
+use crate::errors::{Result, VaporettoError};
 use crate::model::Model;
+#[cfg(feature = "tag-prediction")]
+use crate::model::TagContextWeight;
+#[cfg(feature = "tag-prediction")]
+use crate::oov_tag_model::{for_each_oov_feature, OovTagModel};
 use crate::sentence::{CharacterBoundary, Sentence};
+use crate::template_scorer::TemplateScorer;
 use crate::type_scorer::TypeScorer;
+use crate::user_dictionary::UserDictionary;
 
 #[cfg(feature = "tag-prediction")]
 use crate::utils::SerializableHashMap;
@@ -262,18 +271,62 @@ impl AddAssign<&Self> for PositionalWeightWithTag {
 }
 
 #[cfg(feature = "tag-prediction")]
-#[derive(Decode, Encode)]
+#[derive(Encode)]
 struct TagPredictor {
-    tags: Vec<Vec<String>>,
+    // IDs in the tag vocabulary.
+    tags: Vec<Vec<u32>>,
     bias: WeightVector,
+    prev_token_weights: SerializableHashMap<String, WeightVector>,
+    next_token_weights: SerializableHashMap<String, WeightVector>,
+    // Multipliers of tags with two or more candidates. This is empty if unknown.
+    quantize_multipliers: Vec<f64>,
+}
+
+#[cfg(feature = "tag-prediction")]
+impl Decode for TagPredictor {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let tags = Decode::decode(decoder)?;
+        let bias = Decode::decode(decoder)?;
+        let prev_token_weights = Decode::decode(decoder)?;
+        let next_token_weights = Decode::decode(decoder)?;
+        let quantize_multipliers = Decode::decode(decoder)?;
+        Ok(Self {
+            tags,
+            bias,
+            prev_token_weights,
+            next_token_weights,
+            quantize_multipliers,
+        })
+    }
 }
+#[cfg(feature = "tag-prediction")]
+bincode::impl_borrow_decode!(TagPredictor);
 
 #[cfg(feature = "tag-prediction")]
 impl TagPredictor {
-    pub fn new(tags: Vec<Vec<String>>, bias: Vec<i32>) -> Self {
+    pub fn new(
+        tags: Vec<Vec<u32>>,
+        bias: Vec<i32>,
+        prev_token_weights: Vec<TagContextWeight>,
+        next_token_weights: Vec<TagContextWeight>,
+        quantize_multipliers: Vec<f64>,
+    ) -> Self {
         Self {
             tags,
             bias: bias.into(),
+            prev_token_weights: SerializableHashMap(
+                prev_token_weights
+                    .into_iter()
+                    .map(|w| (w.token, w.weights.into()))
+                    .collect(),
+            ),
+            next_token_weights: SerializableHashMap(
+                next_token_weights
+                    .into_iter()
+                    .map(|w| (w.token, w.weights.into()))
+                    .collect(),
+            ),
+            quantize_multipliers,
         }
     }
 
@@ -283,9 +336,103 @@ impl TagPredictor {
     }
 
     #[inline]
-    pub fn predict<'a>(&'a self, scores: &[i32], tags: &mut [Option<Cow<'a, str>>]) {
+    pub fn has_context(&self) -> bool {
+        !self.prev_token_weights.is_empty() || !self.next_token_weights.is_empty()
+    }
+
+    /// Adds scores of the neighboring tokens. `None` indicates an unknown token.
+    #[inline]
+    pub fn add_context_scores(&self, prev: Option<&str>, next: Option<&str>, ys: &mut [i32]) {
+        if let Some(w) = prev.and_then(|prev| self.prev_token_weights.get(prev)) {
+            w.add_scores(ys);
+        }
+        if let Some(w) = next.and_then(|next| self.next_token_weights.get(next)) {
+            w.add_scores(ys);
+        }
+    }
+
+    /// Returns the confidence of the prediction, which is the sum of the differences between the
+    /// best and the second best raw scores of all tags. Quantized scores are multiplied by
+    /// `default_multiplier` if the multipliers of the model are unknown.
+    pub fn confidence(&self, scores: &[i32], default_multiplier: f64) -> f64 {
+        let mut offset = 0;
+        let mut confidence = 0.;
+        let mut multipliers = self.quantize_multipliers.iter();
+        for tag_cands in &self.tags {
+            if tag_cands.len() >= 2 {
+                let mut best = i32::MIN;
+                let mut second = i32::MIN;
+                for &s in &scores[offset..offset + tag_cands.len()] {
+                    if s > best {
+                        second = best;
+                        best = s;
+                    } else if s > second {
+                        second = s;
+                    }
+                }
+                let multiplier = multipliers.next().copied().unwrap_or(default_multiplier);
+                confidence += f64::from(best - second) * multiplier;
+                offset += tag_cands.len();
+            }
+        }
+        confidence
+    }
+
+    /// Returns `true` if all tag IDs are smaller than the given vocabulary size.
+    pub fn is_valid(&self, vocabulary_size: usize) -> bool {
+        self.tags
+            .iter()
+            .flatten()
+            .all(|&id| usize::try_from(id).unwrap() < vocabulary_size)
+    }
+
+    /// Returns candidates of each tag sorted in descending order of scores.
+    pub fn candidates<'a>(
+        &self,
+        vocabulary: &'a [String],
+        scores: &[i32],
+    ) -> Vec<Vec<TagCandidate<'a>>> {
+        let mut offset = 0;
+        let mut result = Vec::with_capacity(self.tags.len());
+        for tag_cands in &self.tags {
+            if tag_cands.len() >= 2 {
+                let mut candidates: Vec<_> = tag_cands
+                    .iter()
+                    .zip(&scores[offset..offset + tag_cands.len()])
+                    .map(|(&id, &score)| TagCandidate {
+                        tag: &vocabulary[usize::try_from(id).unwrap()],
+                        score,
+                    })
+                    .collect();
+                // Uses a stable sort to keep the order of the prediction for ties.
+                candidates.sort_by_key(|c| Reverse(c.score));
+                result.push(candidates);
+                offset += tag_cands.len();
+            } else {
+                result.push(
+                    tag_cands
+                        .iter()
+                        .map(|&id| TagCandidate {
+                            tag: &vocabulary[usize::try_from(id).unwrap()],
+                            score: 0,
+                        })
+                        .collect(),
+                );
+            }
+        }
+        result
+    }
+
+    #[inline]
+    pub fn predict<'a>(
+        &self,
+        vocabulary: &'a [String],
+        scores: &[i32],
+        tags: &mut [Option<Cow<'a, str>>],
+        tag_ids: &mut [Option<u32>],
+    ) {
         let mut offset = 0;
-        for (tag_cands, tag) in self.tags.iter().zip(tags) {
+        for ((tag_cands, tag), tag_id) in self.tags.iter().zip(tags).zip(tag_ids) {
             if tag_cands.len() >= 2 {
                 let mut idx = 0;
                 let mut max_score = i32::MIN;
@@ -295,24 +442,168 @@ impl TagPredictor {
                         max_score = s;
                     }
                 }
-                tag.replace(Cow::Borrowed(&tag_cands[idx]));
+                let id = tag_cands[idx];
+                tag.replace(Cow::Borrowed(&vocabulary[usize::try_from(id).unwrap()]));
+                tag_id.replace(id);
                 offset += tag_cands.len();
             } else {
-                *tag = tag_cands.first().map(|t| Cow::Borrowed(t.as_str()));
+                *tag_id = tag_cands.first().copied();
+                *tag = tag_id
+                    .map(|id| Cow::Borrowed(vocabulary[usize::try_from(id).unwrap()].as_str()));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tag-prediction")]
+#[derive(Encode)]
+struct OovTagPredictor {
+    predictor: TagPredictor,
+    weights: SerializableHashMap<Vec<u8>, WeightVector>,
+}
+
+#[cfg(feature = "tag-prediction")]
+impl Decode for OovTagPredictor {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let predictor = Decode::decode(decoder)?;
+        let weights = Decode::decode(decoder)?;
+        Ok(Self { predictor, weights })
+    }
+}
+#[cfg(feature = "tag-prediction")]
+bincode::impl_borrow_decode!(OovTagPredictor);
+
+#[cfg(feature = "tag-prediction")]
+impl OovTagPredictor {
+    pub fn new(model: OovTagModel) -> Option<Self> {
+        if model.tags.iter().all(Vec::is_empty) {
+            return None;
+        }
+        Some(Self {
+            predictor: TagPredictor::new(
+                model.tags,
+                model.bias,
+                vec![],
+                vec![],
+                model.quantize_multipliers,
+            ),
+            weights: SerializableHashMap(
+                model
+                    .weights
+                    .into_iter()
+                    .map(|w| (w.key, w.weights.into()))
+                    .collect(),
+            ),
+        })
+    }
+
+    #[inline]
+    pub fn n_tags(&self) -> usize {
+        self.predictor.tags.len()
+    }
+
+    #[inline]
+    pub fn add_scores(&self, surface: &str, char_types: &[u8], scores: &mut Vec<i32>) {
+        let bias = self.predictor.bias();
+        scores.clear();
+        scores.resize(bias.len(), 0);
+        bias.add_scores(scores);
+        for_each_oov_feature(surface, char_types, |key| {
+            if let Some(w) = self.weights.get(key) {
+                w.add_scores(scores);
             }
+        });
+    }
+}
+
+/// A candidate of a tag with its score.
+///
+/// Scores are raw outputs of linear classifiers, so they can only be compared among candidates
+/// of the same tag of the same token.
+#[cfg(feature = "tag-prediction")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TagCandidate<'a> {
+    tag: &'a str,
+    score: i32,
+}
+
+#[cfg(feature = "tag-prediction")]
+impl<'a> TagCandidate<'a> {
+    /// Returns the tag.
+    #[inline]
+    pub const fn tag(&self) -> &'a str {
+        self.tag
+    }
+
+    /// Returns the score of the tag. If the tag has only one candidate, it is determined without
+    /// prediction, and the score is always 0.
+    #[inline]
+    pub const fn score(&self) -> i32 {
+        self.score
+    }
+}
+
+/// Mode of tag prediction specified to [`Predictor::new()`].
+///
+/// `bool` can also be converted into this type, where `true` means
+/// [`TagPrediction::Enabled`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TagPrediction {
+    /// Tags are not predicted.
+    Disabled,
+
+    /// Tags are predicted for tokens included in the model.
+    Enabled,
+
+    /// In addition to [`TagPrediction::Enabled`], tags of out-of-vocabulary tokens are predicted
+    /// if the model contains a tag model for them.
+    WithOov,
+}
+
+impl From<bool> for TagPrediction {
+    fn from(predict_tags: bool) -> Self {
+        if predict_tags {
+            Self::Enabled
+        } else {
+            Self::Disabled
         }
     }
 }
 
+/// Parameters of joint decoding of word boundaries and tags.
+#[cfg(feature = "tag-prediction")]
+struct JointParams {
+    beam_width: usize,
+    tag_weight: f64,
+    token_penalty: f64,
+    // Multiplier of boundary scores used to convert raw tag scores into the scale of boundary
+    // scores.
+    multiplier: f64,
+}
+
 pub struct PredictorData {
     char_scorer: Option<CharScorer>,
     type_scorer: Option<TypeScorer>,
+    // Scorer of the dictionary if the predictor is created by with_swappable_dictionary().
+    // Otherwise, the dictionary is merged into char_scorer, and this is None.
+    dict_scorer: Option<SwappableDictScorer>,
+    template_scorer: Option<TemplateScorer>,
     bias: i32,
 
     #[cfg(feature = "tag-prediction")]
     tag_predictor: Option<SerializableHashMap<String, (u32, TagPredictor)>>,
     #[cfg(feature = "tag-prediction")]
+    oov_tag_predictor: Option<OovTagPredictor>,
+    #[cfg(feature = "tag-prediction")]
     n_tags: usize,
+    #[cfg(feature = "tag-prediction")]
+    tag_vocabulary: Vec<String>,
+    // Multiplier of boundary scores, which is 0 if unknown.
+    #[cfg(feature = "tag-prediction")]
+    quantize_multiplier: f64,
+
+    user_dictionary: Option<UserDictionary>,
 }
 
 impl<'de> BorrowDecode<'de> for PredictorData {
@@ -332,19 +623,37 @@ impl<'de> BorrowDecode<'de> for PredictorData {
         } else {
             None
         };
+        let dict_scorer = BorrowDecode::borrow_decode(decoder)?;
+        let template_scorer = Decode::decode(decoder)?;
         let bias = Decode::decode(decoder)?;
         #[cfg(feature = "tag-prediction")]
         let tag_predictor = Decode::decode(decoder)?;
         #[cfg(feature = "tag-prediction")]
+        let oov_tag_predictor = Decode::decode(decoder)?;
+        #[cfg(feature = "tag-prediction")]
         let n_tags = Decode::decode(decoder)?;
+        #[cfg(feature = "tag-prediction")]
+        let tag_vocabulary: Vec<String> = Decode::decode(decoder)?;
+        #[cfg(feature = "tag-prediction")]
+        let quantize_multiplier = Decode::decode(decoder)?;
+        let user_dictionary = BorrowDecode::borrow_decode(decoder)?;
         Ok(Self {
             char_scorer,
             type_scorer,
+            dict_scorer,
+            template_scorer,
             bias,
             #[cfg(feature = "tag-prediction")]
             tag_predictor,
             #[cfg(feature = "tag-prediction")]
+            oov_tag_predictor,
+            #[cfg(feature = "tag-prediction")]
             n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_vocabulary,
+            #[cfg(feature = "tag-prediction")]
+            quantize_multiplier,
+            user_dictionary,
         })
     }
 }
@@ -364,11 +673,20 @@ impl Encode for PredictorData {
             None
         };
         Encode::encode(&type_scorer_data, encoder)?;
+        Encode::encode(&self.dict_scorer, encoder)?;
+        Encode::encode(&self.template_scorer, encoder)?;
         Encode::encode(&self.bias, encoder)?;
         #[cfg(feature = "tag-prediction")]
         Encode::encode(&self.tag_predictor, encoder)?;
         #[cfg(feature = "tag-prediction")]
+        Encode::encode(&self.oov_tag_predictor, encoder)?;
+        #[cfg(feature = "tag-prediction")]
         Encode::encode(&self.n_tags, encoder)?;
+        #[cfg(feature = "tag-prediction")]
+        Encode::encode(&self.tag_vocabulary, encoder)?;
+        #[cfg(feature = "tag-prediction")]
+        Encode::encode(&self.quantize_multiplier, encoder)?;
+        Encode::encode(&self.user_dictionary, encoder)?;
         Ok(())
     }
 }
@@ -438,12 +756,42 @@ impl Predictor {
     /// # Arguments
     ///
     /// * `model` - A model data.
-    /// * `predict_tags` - If you want to predict tags, set to true.
+    /// * `predict_tags` - If you want to predict tags, set to true. To predict tags of
+    ///   out-of-vocabulary tokens as well, specify [`TagPrediction::WithOov`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error variant when the model is invalid.
+    pub fn new<T>(model: Model, predict_tags: T) -> Result<Self>
+    where
+        T: Into<TagPrediction>,
+    {
+        Self::new_impl(model, predict_tags.into(), false)
+    }
+
+    /// Creates a new predictor whose dictionary can be replaced by
+    /// [`Predictor::set_dictionary()`].
+    ///
+    /// Dictionary words are scored separately from character n-grams, so prediction is slightly
+    /// slower than that of a predictor created by [`Predictor::new()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - A model data.
+    /// * `predict_tags` - If you want to predict tags, set to true. To predict tags of
+    ///   out-of-vocabulary tokens as well, specify [`TagPrediction::WithOov`].
     ///
     /// # Errors
     ///
     /// Returns an error variant when the model is invalid.
-    pub fn new(model: Model, predict_tags: bool) -> Result<Self> {
+    pub fn with_swappable_dictionary<T>(model: Model, predict_tags: T) -> Result<Self>
+    where
+        T: Into<TagPrediction>,
+    {
+        Self::new_impl(model, predict_tags.into(), true)
+    }
+
+    fn new_impl(model: Model, predict_tags: TagPrediction, swappable_dict: bool) -> Result<Self> {
         #[cfg(feature = "tag-prediction")]
         let mut tag_char_ngram_model = vec![];
         #[cfg(feature = "tag-prediction")]
@@ -452,31 +800,64 @@ impl Predictor {
         let mut n_tags = 0;
 
         #[cfg(not(feature = "tag-prediction"))]
-        if predict_tags {
+        if predict_tags != TagPrediction::Disabled {
             panic!("tag prediction is unsupported");
         }
         #[cfg(feature = "tag-prediction")]
-        let tag_predictor = predict_tags.then(|| {
+        let oov_tag_predictor = if predict_tags == TagPrediction::WithOov {
+            OovTagPredictor::new(model.0.oov_tag_model)
+        } else {
+            None
+        };
+        #[cfg(feature = "tag-prediction")]
+        if let Some(oov_tag_predictor) = oov_tag_predictor.as_ref() {
+            n_tags = oov_tag_predictor.n_tags();
+        }
+        #[cfg(feature = "tag-prediction")]
+        let mut valid_tags = oov_tag_predictor
+            .iter()
+            .all(|p| p.predictor.is_valid(model.0.tag_vocabulary.len()));
+        #[cfg(feature = "tag-prediction")]
+        let tag_predictor = (predict_tags != TagPrediction::Disabled).then(|| {
             let mut tag_predictor = HashMap::new();
             for (i, tag_model) in model.0.tag_models.into_iter().enumerate() {
                 n_tags = n_tags.max(tag_model.tags.len());
-                // token does not duplicate in the model.
-                tag_predictor.insert(
-                    tag_model.token,
-                    (
-                        u32::try_from(i).unwrap(),
-                        TagPredictor::new(tag_model.tags, tag_model.bias),
-                    ),
+                let predictor = TagPredictor::new(
+                    tag_model.tags,
+                    tag_model.bias,
+                    tag_model.prev_token_weights,
+                    tag_model.next_token_weights,
+                    tag_model.quantize_multipliers,
                 );
+                valid_tags &= predictor.is_valid(model.0.tag_vocabulary.len());
+                // token does not duplicate in the model.
+                tag_predictor.insert(tag_model.token, (u32::try_from(i).unwrap(), predictor));
                 tag_char_ngram_model.push(tag_model.char_ngram_model);
                 tag_type_ngram_model.push(tag_model.type_ngram_model);
             }
             SerializableHashMap(tag_predictor)
         });
+        #[cfg(feature = "tag-prediction")]
+        if !valid_tags {
+            return Err(VaporettoError::invalid_model(
+                "tag IDs must be smaller than the size of the tag vocabulary",
+            ));
+        }
 
+        // Dictionary words are merged into the character scorer to score them in a single pass
+        // unless the dictionary is replaced later.
+        let (dict_model, dict_scorer) = if swappable_dict {
+            let dict_scorer = DictScorer::new(model.0.dict_model)?;
+            (
+                DictModel::new(vec![]),
+                Some(SwappableDictScorer::new(dict_scorer)),
+            )
+        } else {
+            (model.0.dict_model, None)
+        };
         let char_scorer = CharScorer::new(
             model.0.char_ngram_model,
-            model.0.dict_model,
+            dict_model,
             model.0.char_window_size,
             #[cfg(feature = "tag-prediction")]
             tag_char_ngram_model,
@@ -487,21 +868,83 @@ impl Predictor {
             #[cfg(feature = "tag-prediction")]
             tag_type_ngram_model,
         )?;
+        let template_scorer = TemplateScorer::new(model.0.template_model)?;
         Ok(Self(PredictorData {
             char_scorer,
             type_scorer,
+            dict_scorer,
+            template_scorer,
             bias: model.0.bias,
 
             #[cfg(feature = "tag-prediction")]
             tag_predictor,
             #[cfg(feature = "tag-prediction")]
+            oov_tag_predictor,
+            #[cfg(feature = "tag-prediction")]
             n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_vocabulary: model.0.tag_vocabulary,
+            #[cfg(feature = "tag-prediction")]
+            quantize_multiplier: model.0.quantize_multiplier,
+            user_dictionary: None,
         }))
     }
 
     /// Predicts word boundaries of the given sentence.
+    /// Words in the user dictionary become single tokens, and then boundaries forced by
+    /// [`Sentence::force_token()`], [`Sentence::force_boundary()`], or
+    /// [`Sentence::preserve_whitespace()`] are applied. Words conflicting with the forced
+    /// boundaries are ignored.
     /// If necessary, this function also prepares for predicting tags.
     pub fn predict<'a>(&'a self, sentence: &mut Sentence<'_, 'a>) {
+        self.compute_scores(sentence);
+        for (b, s) in sentence
+            .boundaries
+            .iter_mut()
+            .zip(&sentence.boundary_scores[sentence.score_padding..])
+        {
+            if *s > 0 {
+                *b = CharacterBoundary::WordBoundary;
+            } else {
+                *b = CharacterBoundary::NotWordBoundary;
+            }
+        }
+        if let Some(dict) = self.0.user_dictionary.as_ref() {
+            dict.force_boundaries(sentence, false);
+        }
+        sentence.apply_constraints();
+        sentence.set_predictor(self);
+    }
+
+    /// Predicts word boundaries of the given sentence, keeping boundaries that are already
+    /// annotated.
+    ///
+    /// Only [`CharacterBoundary::Unknown`] positions are predicted or updated by the user
+    /// dictionary, and words conflicting with the annotated boundaries are ignored. Scores are calculated for all positions regardless of the annotation.
+    /// If necessary, this function also prepares for predicting tags.
+    pub fn predict_constrained<'a>(&'a self, sentence: &mut Sentence<'_, 'a>) {
+        self.compute_scores(sentence);
+        if let Some(dict) = self.0.user_dictionary.as_ref() {
+            dict.force_boundaries(sentence, true);
+        }
+        for (b, s) in sentence
+            .boundaries
+            .iter_mut()
+            .zip(&sentence.boundary_scores[sentence.score_padding..])
+        {
+            if *b == CharacterBoundary::Unknown {
+                if *s > 0 {
+                    *b = CharacterBoundary::WordBoundary;
+                } else {
+                    *b = CharacterBoundary::NotWordBoundary;
+                }
+            }
+        }
+        sentence.apply_constraints();
+        sentence.set_predictor(self);
+    }
+
+    fn compute_scores(&self, sentence: &mut Sentence) {
         sentence.score_padding = WEIGHT_FIXED_LEN - 1;
         sentence.boundary_scores.clear();
         sentence
@@ -513,117 +956,548 @@ impl Predictor {
         if let Some(scorer) = self.0.type_scorer.as_ref() {
             scorer.add_scores(sentence);
         }
-        for (b, s) in sentence
-            .boundaries
-            .iter_mut()
-            .zip(&sentence.boundary_scores[sentence.score_padding..])
-        {
-            if *s > 0 {
-                *b = CharacterBoundary::WordBoundary;
+        if let Some(scorer) = self.0.dict_scorer.as_ref() {
+            scorer.add_scores(sentence);
+        }
+        if let Some(scorer) = self.0.template_scorer.as_ref() {
+            scorer.add_scores(sentence);
+        }
+    }
+
+    /// Predicts word boundaries of the given sentence, revising uncertain boundaries using tag
+    /// models.
+    ///
+    /// This function first predicts word boundaries in the same way as [`Predictor::predict()`].
+    /// Then, for each run of boundaries whose absolute scores are smaller than `margin`, it
+    /// searches for the segmentation maximizing the sum of the boundary scores and the tag scores
+    /// of the resulting tokens using beam search. The tag score of a token is `tag_weight` times
+    /// the confidence of its tags minus `token_penalty`, where the confidence is the sum of the
+    /// differences between the best and the second best raw scores of its tags, and it is 0 if
+    /// the model cannot predict tags of the token. Since the confidence is never negative, the
+    /// penalty prevents the search from splitting tokens only to gain confidences. Tag scores
+    /// are converted into the scale of boundary scores using the quantization multipliers stored
+    /// in the model. Models without multipliers, such as those created by older versions, use
+    /// quantized scores as they are.
+    ///
+    /// The scores of revised boundaries whose decisions are changed are negated, so the signs of
+    /// [`Sentence::boundary_scores()`] agree with the resulting boundaries. Features of
+    /// neighboring tokens are calculated from the boundaries before the revision. Boundaries
+    /// forced by [`Sentence::force_token()`], [`Sentence::force_boundary()`], or the user
+    /// dictionary are not revised.
+    ///
+    /// # Arguments
+    ///
+    /// * `sentence` - A sentence.
+    /// * `margin` - Boundaries whose absolute scores are smaller than this value are revised.
+    /// * `beam_width` - The number of hypotheses kept during the search. 0 is treated as 1.
+    /// * `tag_weight` - The multiplier of the confidences of tags.
+    /// * `token_penalty` - The penalty of each token in the scale of raw tag scores.
+    ///
+    /// # Panics
+    ///
+    /// The predictor must be created with `predict_tags = true`.
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    pub fn predict_joint<'a>(
+        &'a self,
+        sentence: &mut Sentence<'_, 'a>,
+        margin: u32,
+        beam_width: usize,
+        tag_weight: f64,
+        token_penalty: f64,
+    ) {
+        assert!(
+            self.0.tag_predictor.is_some(),
+            "this predictor is created with predict_tags = false"
+        );
+
+        self.predict(sentence);
+        let params = JointParams {
+            beam_width: beam_width.max(1),
+            tag_weight,
+            token_penalty,
+            multiplier: if self.0.quantize_multiplier > 0. {
+                self.0.quantize_multiplier
             } else {
-                *b = CharacterBoundary::NotWordBoundary;
+                1.
+            },
+        };
+        let mut scores = vec![];
+        let mut i = 0;
+        while i < sentence.boundaries.len() {
+            let run_start = i;
+            while i < sentence.boundaries.len()
+                && sentence.boundary_scores[sentence.score_padding + i].unsigned_abs() < margin
+                && sentence
+                    .constraints
+                    .get(i)
+                    .map_or(true, |&c| c == CharacterBoundary::Unknown)
+            {
+                i += 1;
+            }
+            if run_start == i {
+                i += 1;
+            } else {
+                self.revise_boundaries(sentence, run_start, i, &params, &mut scores);
+            }
+        }
+        if let Some(dict) = self.0.user_dictionary.as_ref() {
+            dict.force_boundaries(sentence, false);
+        }
+        sentence.apply_constraints();
+    }
+
+    /// Returns the tag score of the token in the scale of boundary scores.
+    #[cfg(feature = "tag-prediction")]
+    fn joint_tag_score(
+        &self,
+        sentence: &Sentence,
+        start: usize,
+        end: usize,
+        prev_start: Option<usize>,
+        params: &JointParams,
+        scores: &mut Vec<i32>,
+    ) -> f64 {
+        let confidence = self
+            .tag_scores(sentence, start, end, prev_start, scores)
+            .map_or(0., |tag_predictor| {
+                tag_predictor.confidence(scores, params.multiplier)
+            });
+        let weighted_confidence = params.tag_weight * confidence;
+        (weighted_confidence - params.token_penalty) / params.multiplier
+    }
+
+    /// Revises boundaries in the range `[run_start, run_end)` using beam search.
+    #[cfg(feature = "tag-prediction")]
+    fn revise_boundaries(
+        &self,
+        sentence: &mut Sentence,
+        run_start: usize,
+        run_end: usize,
+        params: &JointParams,
+        scores: &mut Vec<i32>,
+    ) {
+        struct Hypothesis {
+            score: f64,
+            token_start: usize,
+            prev_start: Option<usize>,
+            boundaries: Vec<CharacterBoundary>,
+        }
+
+        // Boundaries outside the run are fixed, so the first and the last tokens affected by the
+        // run start and end at the nearest word boundaries.
+        let token_start = sentence.boundaries[..run_start]
+            .iter()
+            .rposition(|&b| b == CharacterBoundary::WordBoundary)
+            .map_or(0, |i| i + 1);
+        let token_end = sentence.boundaries[run_end..]
+            .iter()
+            .position(|&b| b == CharacterBoundary::WordBoundary)
+            .map_or(sentence.len(), |i| run_end + i + 1);
+
+        let mut hyps = vec![Hypothesis {
+            score: 0.,
+            token_start,
+            prev_start: Self::prev_token_start(sentence, token_start),
+            boundaries: vec![],
+        }];
+        for i in run_start..run_end {
+            let boundary_score = f64::from(sentence.boundary_scores[sentence.score_padding + i]);
+            let mut next_hyps = Vec::with_capacity(hyps.len() * 2);
+            for hyp in hyps {
+                let tag_score = self.joint_tag_score(
+                    sentence,
+                    hyp.token_start,
+                    i + 1,
+                    hyp.prev_start,
+                    params,
+                    scores,
+                );
+                let mut boundaries = hyp.boundaries.clone();
+                boundaries.push(CharacterBoundary::WordBoundary);
+                let split = Hypothesis {
+                    score: hyp.score + boundary_score + tag_score,
+                    token_start: i + 1,
+                    prev_start: Some(hyp.token_start),
+                    boundaries,
+                };
+                let mut boundaries = hyp.boundaries;
+                boundaries.push(CharacterBoundary::NotWordBoundary);
+                let merged = Hypothesis {
+                    score: hyp.score - boundary_score,
+                    boundaries,
+                    ..hyp
+                };
+                // The hypothesis following the prediction is pushed first, so the stable sort
+                // prefers it for ties.
+                if boundary_score > 0. {
+                    next_hyps.push(split);
+                    next_hyps.push(merged);
+                } else {
+                    next_hyps.push(merged);
+                    next_hyps.push(split);
+                }
+            }
+            next_hyps.sort_by(|a, b| b.score.total_cmp(&a.score));
+            next_hyps.truncate(params.beam_width);
+            hyps = next_hyps;
+        }
+        let mut best_score = f64::NEG_INFINITY;
+        let mut best_boundaries = vec![];
+        for hyp in hyps {
+            let score = hyp.score
+                + self.joint_tag_score(
+                    sentence,
+                    hyp.token_start,
+                    token_end,
+                    hyp.prev_start,
+                    params,
+                    scores,
+                );
+            if score > best_score {
+                best_score = score;
+                best_boundaries = hyp.boundaries;
+            }
+        }
+        for (i, b) in (run_start..run_end).zip(best_boundaries) {
+            if sentence.boundaries[i] != b {
+                sentence.boundaries[i] = b;
+                let score = &mut sentence.boundary_scores[sentence.score_padding + i];
+                *score = score.saturating_neg();
             }
         }
-        sentence.set_predictor(self);
     }
 
     #[cfg(feature = "tag-prediction")]
     pub(crate) fn predict_tags<'a>(&'a self, sentence: &mut Sentence<'_, 'a>) {
-        let tag_predictor = self
-            .0
-            .tag_predictor
-            .as_ref()
-            .expect("this predictor is created with predict_tags = false");
+        assert!(
+            self.0.tag_predictor.is_some(),
+            "this predictor is created with predict_tags = false"
+        );
 
-        if self.0.n_tags == 0 {
+        let n_tags = self.n_tags();
+        if n_tags == 0 {
             return;
         }
         let mut scores = vec![];
         let mut range_start = Some(0);
-        sentence.n_tags = self.0.n_tags;
+        // The start of the previous token. The previous token of the first token is empty.
+        let mut prev_start = Some(0);
+        sentence.n_tags = n_tags;
         sentence.tags.clear();
-        sentence.tags.resize(sentence.len() * self.0.n_tags, None);
-        for (i, &b) in sentence.boundaries.iter().enumerate() {
+        sentence.tags.resize(sentence.len() * n_tags, None);
+        sentence.tag_ids.clear();
+        sentence.tag_ids.resize(sentence.len() * n_tags, None);
+        for i in 0..sentence.len() {
+            // The end of the sentence is always a word boundary.
+            let b = sentence
+                .boundaries
+                .get(i)
+                .copied()
+                .unwrap_or(CharacterBoundary::WordBoundary);
             if b == CharacterBoundary::Unknown {
                 range_start.take();
+                prev_start.take();
             } else if b == CharacterBoundary::WordBoundary {
-                if let Some(&range_start) = range_start.as_ref() {
-                    let token = sentence.text_substring(range_start, i + 1);
-                    if let Some((token_id, tag_predictor)) = tag_predictor.get(token) {
-                        scores.clear();
-                        scores.resize(tag_predictor.bias().len(), 0);
-                        tag_predictor.bias().add_scores(&mut scores);
-                        if let Some(scorer) = self.0.char_scorer.as_ref() {
-                            debug_assert!(i < sentence.char_pma_states.len());
-                            // token_id is always smaller than tag_weight.len() because
-                            // tag_predictor is created to contain such values in the new()
-                            // function.
-                            unsafe {
-                                scorer.add_tag_scores(*token_id, i, sentence, &mut scores);
-                            }
-                        }
-                        if let Some(scorer) = self.0.type_scorer.as_ref() {
-                            debug_assert!(i < sentence.type_pma_states.len());
-                            // token_id is always smaller than tag_weight.len() because
-                            // tag_predictor is created to contain such values in the new()
-                            // function.
-                            unsafe {
-                                scorer.add_tag_scores(*token_id, i, sentence, &mut scores);
-                            }
-                        }
+                if let Some(start) = range_start {
+                    if let Some(tag_predictor) =
+                        self.tag_scores(sentence, start, i + 1, prev_start, &mut scores)
+                    {
                         tag_predictor.predict(
+                            &self.0.tag_vocabulary,
                             &scores,
-                            &mut sentence.tags[i * self.0.n_tags..(i + 1) * self.0.n_tags],
+                            &mut sentence.tags[i * n_tags..(i + 1) * n_tags],
+                            &mut sentence.tag_ids[i * n_tags..(i + 1) * n_tags],
                         );
                     }
                 }
-                range_start.replace(i + 1);
+                prev_start = range_start.replace(i + 1);
             }
         }
-        if let Some(&range_start) = range_start.as_ref() {
-            let token = sentence.text_substring(range_start, sentence.len());
-            if let Some((token_id, tag_predictor)) = tag_predictor.get(token) {
-                scores.clear();
-                scores.resize(tag_predictor.bias().len(), 0);
-                tag_predictor.bias().add_scores(&mut scores);
-                if let Some(scorer) = self.0.char_scorer.as_ref() {
-                    debug_assert!(sentence.len() <= sentence.char_pma_states.len());
-                    // token_id is always smaller than tag_weight.len() because tag_predictor is
-                    // created to contain such values in the new() function.
-                    unsafe {
-                        scorer.add_tag_scores(*token_id, sentence.len() - 1, sentence, &mut scores);
-                    }
+        if let Some(dict) = self.0.user_dictionary.as_ref() {
+            dict.fill_tags(sentence);
+        }
+    }
+
+    /// Returns the maximum number of tags including tags of the user dictionary.
+    #[cfg(feature = "tag-prediction")]
+    fn n_tags(&self) -> usize {
+        self.0
+            .user_dictionary
+            .as_ref()
+            .map_or(self.0.n_tags, |dict| self.0.n_tags.max(dict.n_tags()))
+    }
+
+    /// Returns candidates of tags of the token in the given range.
+    #[cfg(feature = "tag-prediction")]
+    pub(crate) fn tag_candidates<'a>(
+        &'a self,
+        sentence: &Sentence,
+        start: usize,
+        end: usize,
+    ) -> Vec<Vec<TagCandidate<'a>>> {
+        assert!(
+            self.0.tag_predictor.is_some(),
+            "this predictor is created with predict_tags = false"
+        );
+
+        let prev_start = Self::prev_token_start(sentence, start);
+        let mut scores = vec![];
+        let mut candidates = self
+            .tag_scores(sentence, start, end, prev_start, &mut scores)
+            .map_or_else(Vec::new, |tag_predictor| {
+                tag_predictor.candidates(&self.0.tag_vocabulary, &scores)
+            });
+        candidates.resize_with(self.n_tags(), Vec::new);
+        candidates
+    }
+
+    /// Calculates scores of tags of the token in the given range, and returns the tag predictor
+    /// corresponding to the scores. If the token is unknown, returns `None`.
+    #[cfg(feature = "tag-prediction")]
+    fn tag_scores(
+        &self,
+        sentence: &Sentence,
+        start: usize,
+        end: usize,
+        prev_start: Option<usize>,
+        scores: &mut Vec<i32>,
+    ) -> Option<&TagPredictor> {
+        let token = sentence.text_substring(start, end);
+        let tag_predictor = self.0.tag_predictor.as_ref()?;
+        if let Some((token_id, tag_predictor)) = tag_predictor.get(token) {
+            scores.clear();
+            scores.resize(tag_predictor.bias().len(), 0);
+            tag_predictor.bias().add_scores(scores);
+            if let Some(scorer) = self.0.char_scorer.as_ref() {
+                debug_assert!(end <= sentence.char_pma_states.len());
+                // token_id is always smaller than tag_weight.len() because tag_predictor is
+                // created to contain such values in the new() function.
+                unsafe {
+                    scorer.add_tag_scores(*token_id, end - 1, sentence, scores);
                 }
-                if let Some(scorer) = self.0.type_scorer.as_ref() {
-                    debug_assert!(sentence.len() <= sentence.type_pma_states.len());
-                    // token_id is always smaller than tag_weight.len() because tag_predictor is
-                    // created to contain such values in the new() function.
-                    unsafe {
-                        scorer.add_tag_scores(*token_id, sentence.len() - 1, sentence, &mut scores);
-                    }
+            }
+            if let Some(scorer) = self.0.type_scorer.as_ref() {
+                debug_assert!(end <= sentence.type_pma_states.len());
+                // token_id is always smaller than tag_weight.len() because tag_predictor is
+                // created to contain such values in the new() function.
+                unsafe {
+                    scorer.add_tag_scores(*token_id, end - 1, sentence, scores);
                 }
-                let i = sentence.len() - 1;
-                tag_predictor.predict(&scores, &mut sentence.tags[i * self.0.n_tags..]);
             }
+            if tag_predictor.has_context() {
+                let prev = prev_start.map(|s| sentence.text_substring(s, start));
+                let next = Self::next_token_surface(sentence, end);
+                tag_predictor.add_context_scores(prev, next, scores);
+            }
+            Some(tag_predictor)
+        } else if let Some(oov_tag_predictor) = self.0.oov_tag_predictor.as_ref() {
+            oov_tag_predictor.add_scores(token, &sentence.char_types[start..end], scores);
+            Some(&oov_tag_predictor.predictor)
+        } else {
+            None
         }
     }
 
-    /// Serializes the predictor into a Vec.
-    pub fn serialize_to_vec(&self) -> Result<Vec<u8>> {
-        let config = bincode::config::standard();
-        let result = bincode::encode_to_vec(&self.0, config)?;
-        Ok(result)
+    /// Returns the start of the token ending at the given position, or `None` if the start of
+    /// the token is unknown. If the position is the beginning of the sentence, returns the same
+    /// position, which indicates an empty token.
+    #[cfg(feature = "tag-prediction")]
+    fn prev_token_start(sentence: &Sentence, end: usize) -> Option<usize> {
+        if end == 0 {
+            return Some(0);
+        }
+        for i in (0..end - 1).rev() {
+            match sentence.boundaries[i] {
+                CharacterBoundary::WordBoundary => return Some(i + 1),
+                CharacterBoundary::Unknown => return None,
+                CharacterBoundary::NotWordBoundary => {}
+            }
+        }
+        Some(0)
     }
 
-    /// Deserializes a predictor from a given slice and returns a tuple of the predictor and the remaining slice.
+    /// Returns the surface of the token starting at the given position, or `None` if the end of
+    /// the token is unknown. If the position is the end of the sentence, returns an empty string.
+    #[cfg(feature = "tag-prediction")]
+    fn next_token_surface<'s>(sentence: &'s Sentence, start: usize) -> Option<&'s str> {
+        for (i, &b) in sentence.boundaries.iter().enumerate().skip(start) {
+            match b {
+                CharacterBoundary::WordBoundary => {
+                    return Some(sentence.text_substring(start, i + 1));
+                }
+                CharacterBoundary::Unknown => return None,
+                CharacterBoundary::NotWordBoundary => {}
+            }
+        }
+        Some(sentence.text_substring(start, sentence.len()))
+    }
+
+    /// Replaces the dictionary of the predictor with the given words.
     ///
-    /// # Safety
+    /// Unlike [`Model::replace_dictionary()`], this function only rebuilds the scorer of the
+    /// dictionary from the given words, so the predictor does not need to be created again. The
+    /// predictor must be created by [`Predictor::with_swappable_dictionary()`].
     ///
-    /// The given data must be a correct predictor exported by [`Predictor::serialize_to_vec()`]
-    /// function.
-    pub unsafe fn deserialize_from_slice_unchecked(data: &[u8]) -> Result<(Self, &[u8])> {
-        let config = bincode::config::standard();
-        // Deserialization is unsafe because the automaton will not be verified.
+    /// With the `std` feature, the dictionary can be replaced while other threads are using the
+    /// predictor. Predictions that have already started keep using the previous dictionary.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error variant when the dictionary is invalid, or the predictor is created by
+    /// [`Predictor::new()`].
+    #[cfg(feature = "std")]
+    pub fn set_dictionary(&self, dict: Vec<WordWeightRecord>) -> Result<()> {
+        let dict_scorer = self
+            .0
+            .dict_scorer
+            .as_ref()
+            .ok_or_else(Self::unswappable_dictionary_error)?;
+        dict_scorer.replace(DictScorer::new(DictModel::new(dict))?);
+        Ok(())
+    }
+
+    /// Replaces the dictionary of the predictor with the given words.
+    ///
+    /// Unlike [`Model::replace_dictionary()`], this function only rebuilds the scorer of the
+    /// dictionary from the given words, so the predictor does not need to be created again. The
+    /// predictor must be created by [`Predictor::with_swappable_dictionary()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error variant when the dictionary is invalid, or the predictor is created by
+    /// [`Predictor::new()`].
+    #[cfg(not(feature = "std"))]
+    pub fn set_dictionary(&mut self, dict: Vec<WordWeightRecord>) -> Result<()> {
+        let dict_scorer = self
+            .0
+            .dict_scorer
+            .as_mut()
+            .ok_or_else(Self::unswappable_dictionary_error)?;
+        dict_scorer.replace(DictScorer::new(DictModel::new(dict))?);
+        Ok(())
+    }
+
+    fn unswappable_dictionary_error() -> VaporettoError {
+        VaporettoError::invalid_argument(
+            "self",
+            "the predictor must be created by with_swappable_dictionary()",
+        )
+    }
+
+    /// Sets a user dictionary whose words are forced to be single tokens, and returns the
+    /// previous one.
+    ///
+    /// Tags of the dictionary are annotated to tokens of the words by [`Sentence::fill_tags()`],
+    /// overwriting predicted tags. `None` tags are predicted by the model.
+    ///
+    #[cfg_attr(
+        feature = "std",
+        doc = "
+# Examples
+
+```
+use std::fs::File;
+
+use vaporetto::{ConflictResolution, Model, Predictor, Sentence, UserDictionary, UserWord};
+
+let f = File::open(\"../resources/model.bin\").unwrap();
+let model = Model::read(f).unwrap();
+let mut predictor = Predictor::new(model, false).unwrap();
+let dict = UserDictionary::new(
+    vec![UserWord::new(\"火星猫\".into(), vec![]).unwrap()],
+    ConflictResolution::LongestMatch,
+).unwrap();
+predictor.set_user_dictionary(Some(dict));
+
+let mut s = Sentence::from_raw(\"まぁ社長は火星猫だ\").unwrap();
+predictor.predict(&mut s);
+let mut buf = String::new();
+s.write_tokenized_text(&mut buf);
+assert_eq!(\"まぁ 社長 は 火星猫 だ\", buf);
+```
+"
+    )]
+    pub fn set_user_dictionary(
+        &mut self,
+        dictionary: Option<UserDictionary>,
+    ) -> Option<UserDictionary> {
+        core::mem::replace(&mut self.0.user_dictionary, dictionary)
+    }
+
+    /// Returns a reference to the user dictionary.
+    pub const fn user_dictionary(&self) -> Option<&UserDictionary> {
+        self.0.user_dictionary.as_ref()
+    }
+
+    /// Returns the tag vocabulary of the model. A tag ID is an index of this slice.
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    pub fn tag_vocabulary(&self) -> &[String] {
+        &self.0.tag_vocabulary
+    }
+
+    /// Returns the ID of the given tag, or `None` if the tag is not in the tag vocabulary.
+    ///
+    /// This function searches the vocabulary linearly. IDs of predicted tags can be obtained by
+    /// [`Token::tag_ids()`](crate::Token::tag_ids) without searching.
+    ///
+    #[cfg_attr(
+        feature = "std",
+        doc = "
+# Examples
+
+```
+use std::fs::File;
+
+use vaporetto::{Model, Predictor};
+
+let f = File::open(\"../resources/model.bin\").unwrap();
+let model = Model::read(f).unwrap();
+let predictor = Predictor::new(model, true).unwrap();
+
+let id = predictor.tag_id(\"名詞\").unwrap();
+assert_eq!(Some(\"名詞\"), predictor.tag_str(id));
+assert_eq!(None, predictor.tag_id(\"存在しない品詞\"));
+```
+"
+    )]
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    pub fn tag_id(&self, tag: &str) -> Option<u32> {
+        self.0
+            .tag_vocabulary
+            .iter()
+            .position(|t| t == tag)
+            .map(|i| u32::try_from(i).unwrap())
+    }
+
+    /// Returns the tag of the given ID, or `None` if the ID is out of the tag vocabulary.
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    pub fn tag_str(&self, id: u32) -> Option<&str> {
+        self.0
+            .tag_vocabulary
+            .get(usize::try_from(id).ok()?)
+            .map(String::as_str)
+    }
+
+    /// Serializes the predictor into a Vec.
+    pub fn serialize_to_vec(&self) -> Result<Vec<u8>> {
+        let config = bincode::config::standard();
+        let result = bincode::encode_to_vec(&self.0, config)?;
+        Ok(result)
+    }
+
+    /// Deserializes a predictor from a given slice and returns a tuple of the predictor and the remaining slice.
+    ///
+    /// # Safety
+    ///
+    /// The given data must be a correct predictor exported by [`Predictor::serialize_to_vec()`]
+    /// function.
+    pub unsafe fn deserialize_from_slice_unchecked(data: &[u8]) -> Result<(Self, &[u8])> {
+        let config = bincode::config::standard();
+        // Deserialization is unsafe because the automaton will not be verified.
         let (predictor_data, size) = bincode::borrow_decode_from_slice(data, config)?;
         Ok((Self(predictor_data), &data[size..]))
     }
@@ -633,9 +1507,9 @@ impl Predictor {
 mod tests {
     use super::*;
 
-    use crate::dict_model::{DictModel, WordWeightRecord};
     use crate::model::TagModel;
     use crate::ngram_model::{NgramData, NgramModel, TagNgramData, TagNgramModel, TagWeight};
+    use crate::user_dictionary::{ConflictResolution, UserWord};
     use crate::CharacterBoundary::*;
     use crate::CharacterType::*;
 
@@ -723,7 +1597,9 @@ mod tests {
         // dict:
         //   人:           19  20          19  20
         //   地球:                 21 -22  23
-        Model::new(
+        // tag vocabulary:
+        //   0: 名詞, 1: 接尾辞, 2: ジン, 3: ヒト, 4: マンホーム, 5: チキュー, 6: 助詞
+        let mut model = Model::new(
             NgramModel(vec![
                 NgramData {
                     ngram: "この人".into(),
@@ -762,10 +1638,7 @@ mod tests {
             vec![
                 TagModel {
                     token: "人".into(),
-                    tags: vec![
-                        vec!["名詞".into(), "接尾辞".into()],
-                        vec!["ジン".into(), "ヒト".into()],
-                    ],
+                    tags: vec![vec![0, 1], vec![2, 3]],
                     char_ngram_model: TagNgramModel(vec![TagNgramData {
                         ngram: "は地球人".into(),
                         weights: vec![TagWeight {
@@ -781,13 +1654,13 @@ mod tests {
                         }],
                     }]),
                     bias: vec![40, 41, 42, 43],
+                    prev_token_weights: vec![],
+                    next_token_weights: vec![],
+                    quantize_multipliers: vec![],
                 },
                 TagModel {
                     token: "地球".into(),
-                    tags: vec![
-                        vec!["名詞".into()],
-                        vec!["マンホーム".into(), "チキュー".into()],
-                    ],
+                    tags: vec![vec![0], vec![4, 5]],
                     char_ngram_model: TagNgramModel(vec![TagNgramData {
                         ngram: "は地球人".into(),
                         weights: vec![TagWeight {
@@ -797,9 +1670,25 @@ mod tests {
                     }]),
                     type_ngram_model: TagNgramModel(vec![]),
                     bias: vec![46, 47],
+                    prev_token_weights: vec![],
+                    next_token_weights: vec![],
+                    quantize_multipliers: vec![],
                 },
             ],
-        )
+        );
+        model.0.tag_vocabulary = [
+            "名詞",
+            "接尾辞",
+            "ジン",
+            "ヒト",
+            "マンホーム",
+            "チキュー",
+            "助詞",
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+        model
     }
 
     #[test]
@@ -823,6 +1712,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_predict_constrained() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, false).unwrap();
+        let mut sentence = Sentence::from_partial_annotation("こ|の 人 は 地-球 人-だ").unwrap();
+        predictor.predict_constrained(&mut sentence);
+        assert_eq!(&[-22, 54, 58, 43, -54, 68, 48], sentence.boundary_scores(),);
+        assert_eq!(
+            &[
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                NotWordBoundary
+            ],
+            sentence.boundaries(),
+        );
+    }
+
+    #[test]
+    fn test_predict_forced() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, false).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        sentence.force_token(12..21).unwrap();
+        sentence.force_boundary(3).unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(&[-22, 54, 58, 43, -54, 68, 48], sentence.boundary_scores(),);
+        assert_eq!(
+            &[
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary
+            ],
+            sentence.boundaries(),
+        );
+    }
+
+    #[test]
+    fn test_predict_preserve_whitespace() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, false).unwrap();
+        let mut sentence = Sentence::from_raw("この人 は地球人だ").unwrap();
+        sentence.preserve_whitespace().unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(" ", sentence.text_substring(3, 4));
+        assert_eq!(WordBoundary, sentence.boundaries()[2]);
+        assert_eq!(WordBoundary, sentence.boundaries()[3]);
+        assert!(sentence
+            .iter_tokens()
+            .all(|token| token.surface() != " " && !token.surface().contains(' ')));
+        assert_eq!("この人 は地球人だ", sentence.as_raw_text());
+    }
+
+    #[test]
+    fn test_predict_constrained_without_annotation() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, false).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict_constrained(&mut sentence);
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary
+            ],
+            sentence.boundaries(),
+        );
+    }
+
     #[cfg(feature = "tag-prediction")]
     #[test]
     fn test_predict_tags() {
@@ -867,6 +1836,277 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_tags_with_context() {
+        let mut model = create_test_model();
+        model.0.tag_models[0].prev_token_weights = vec![TagContextWeight {
+            token: "地球".into(),
+            weights: vec![1000, 0, 0, 1000],
+        }];
+        model.0.tag_models[0].next_token_weights = vec![TagContextWeight {
+            token: "は".into(),
+            weights: vec![0, 1000, 1000, 0],
+        }];
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        sentence.fill_tags();
+        assert_eq!(
+            &[
+                None,
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("接尾辞")),
+                Some(Cow::Borrowed("ジン")),
+                None,
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                Some(Cow::Borrowed("チキュー")),
+                Some(Cow::Borrowed("名詞")),
+                Some(Cow::Borrowed("ヒト")),
+                None,
+                None,
+            ],
+            sentence.tags()
+        );
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_tag_candidates() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        let candidates: Vec<_> = sentence
+            .iter_tokens()
+            .map(|token| token.tag_candidates())
+            .collect();
+        assert_eq!(
+            vec![
+                vec![vec![], vec![]],
+                vec![
+                    vec![
+                        TagCandidate {
+                            tag: "名詞",
+                            score: 76
+                        },
+                        TagCandidate {
+                            tag: "接尾辞",
+                            score: 4
+                        }
+                    ],
+                    vec![
+                        TagCandidate {
+                            tag: "ヒト",
+                            score: 82
+                        },
+                        TagCandidate {
+                            tag: "ジン",
+                            score: 4
+                        }
+                    ],
+                ],
+                vec![vec![], vec![]],
+                vec![
+                    vec![TagCandidate {
+                        tag: "名詞",
+                        score: 0
+                    }],
+                    vec![
+                        TagCandidate {
+                            tag: "チキュー",
+                            score: 92
+                        },
+                        TagCandidate {
+                            tag: "マンホーム",
+                            score: 2
+                        }
+                    ],
+                ],
+                vec![
+                    vec![
+                        TagCandidate {
+                            tag: "接尾辞",
+                            score: 74
+                        },
+                        TagCandidate {
+                            tag: "名詞",
+                            score: 8
+                        }
+                    ],
+                    vec![
+                        TagCandidate {
+                            tag: "ジン",
+                            score: 76
+                        },
+                        TagCandidate {
+                            tag: "ヒト",
+                            score: 8
+                        }
+                    ],
+                ],
+                vec![vec![], vec![]],
+            ],
+            candidates,
+        );
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    fn create_test_model_for_joint_decoding() -> Model {
+        let mut model = create_test_model();
+        model.0.tag_models.push(TagModel {
+            token: "地球人".into(),
+            tags: vec![vec![0, 1]],
+            char_ngram_model: TagNgramModel(vec![]),
+            type_ngram_model: TagNgramModel(vec![]),
+            bias: vec![500, 0],
+            prev_token_weights: vec![],
+            next_token_weights: vec![],
+            quantize_multipliers: vec![0.2],
+        });
+        model.0.quantize_multiplier = 0.1;
+        model
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_joint() {
+        let model = create_test_model_for_joint_decoding();
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict_joint(&mut sentence, 70, 8, 1., 0.);
+        // The score of the revised boundary is negated.
+        assert_eq!(&[-22, 54, 58, 43, -54, -68, 48], sentence.boundary_scores());
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+            ],
+            sentence.boundaries(),
+        );
+        sentence.fill_tags();
+        assert_eq!(Some(Cow::Borrowed("名詞")), sentence.tags()[12]);
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_joint_quantize_multipliers() {
+        let mut model = create_test_model_for_joint_decoding();
+        // The confidence of "地球人" becomes 500 * 0.01 / 0.1 = 50 in the scale of boundary
+        // scores, which is not enough to merge "地球" and "人".
+        model.0.tag_models.last_mut().unwrap().quantize_multipliers = vec![0.01];
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict_joint(&mut sentence, 70, 8, 1., 0.);
+        assert_eq!(WordBoundary, sentence.boundaries()[5]);
+        assert_eq!(68, sentence.boundary_scores()[5]);
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_joint_certain_boundaries() {
+        let model = create_test_model_for_joint_decoding();
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+
+        // The boundary between "球" and "人" is not revised.
+        predictor.predict_joint(&mut sentence, 60, 8, 1., 0.);
+        assert_eq!(WordBoundary, sentence.boundaries()[5]);
+
+        // Tags are ignored.
+        predictor.predict_joint(&mut sentence, 70, 8, 0., 0.);
+        assert_eq!(WordBoundary, sentence.boundaries()[5]);
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_joint_token_penalty() {
+        let model = create_test_model_for_joint_decoding();
+        let predictor = Predictor::new(model, true).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+
+        // A large penalty merges all uncertain boundaries.
+        predictor.predict_joint(&mut sentence, 70, 8, 1., 1000.);
+        assert_eq!(&[NotWordBoundary; 7], sentence.boundaries());
+        assert_eq!(
+            &[-22, -54, -58, -43, -54, -68, -48],
+            sentence.boundary_scores()
+        );
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    fn create_test_model_with_oov() -> Model {
+        let mut model = create_test_model();
+        let mut key = None;
+        for_each_oov_feature("こ", &[Hiragana as u8], |k| {
+            key.get_or_insert_with(|| k.to_vec());
+        });
+        model.0.oov_tag_model = OovTagModel {
+            tags: vec![vec![0, 6]],
+            bias: vec![0, 10],
+            weights: vec![crate::oov_tag_model::OovTagWeight {
+                key: key.unwrap(),
+                weights: vec![100, 0],
+            }],
+            quantize_multipliers: vec![],
+        };
+        model
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_oov_tags() {
+        let model = create_test_model_with_oov();
+        let predictor = Predictor::new(model, TagPrediction::WithOov).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        sentence.fill_tags();
+        assert_eq!(
+            &[
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                None,
+                Some(Cow::Borrowed("名詞")),
+                Some(Cow::Borrowed("ヒト")),
+                Some(Cow::Borrowed("助詞")),
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                Some(Cow::Borrowed("チキュー")),
+                Some(Cow::Borrowed("接尾辞")),
+                Some(Cow::Borrowed("ジン")),
+                Some(Cow::Borrowed("助詞")),
+                None,
+            ],
+            sentence.tags()
+        );
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_oov_tags_disabled() {
+        let model = create_test_model_with_oov();
+        let predictor = Predictor::new(model, TagPrediction::Enabled).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        sentence.fill_tags();
+        assert_eq!(None, sentence.tags()[2]);
+        assert_eq!(None, sentence.tags()[6]);
+        assert_eq!(None, sentence.tags()[14]);
+    }
+
     #[test]
     fn test_serialization() {
         let model = create_test_model();
@@ -890,6 +2130,193 @@ mod tests {
         );
     }
 
+    #[test]
+    // set_dictionary() takes a mutable reference without the std feature.
+    #[cfg_attr(feature = "std", allow(unused_mut))]
+    fn test_set_dictionary() {
+        let model = create_test_model();
+        let mut predictor = Predictor::new(model, false).unwrap();
+
+        // The dictionary of the model is merged into the character scorer.
+        assert!(predictor.0.dict_scorer.is_none());
+        assert!(predictor.set_dictionary(vec![]).is_err());
+
+        let model = create_test_model();
+        let mut predictor = Predictor::with_swappable_dictionary(model, false).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(&[-22, 54, 58, 43, -54, 68, 48], sentence.boundary_scores());
+
+        predictor.set_dictionary(vec![]).unwrap();
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(&[-22, 35, 38, 22, -32, 26, 28], sentence.boundary_scores());
+
+        predictor
+            .set_dictionary(vec![WordWeightRecord::new(
+                "地球人".into(),
+                vec![0, -100, -100, 0],
+                "".into(),
+            )
+            .unwrap()])
+            .unwrap();
+        let data = predictor.serialize_to_vec().unwrap();
+        let (predictor, _) = unsafe { Predictor::deserialize_from_slice_unchecked(&data).unwrap() };
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(
+            &[-22, 35, 38, 22, -132, -74, 28],
+            sentence.boundary_scores()
+        );
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary
+            ],
+            sentence.boundaries(),
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_set_dictionary_shared() {
+        let model = create_test_model();
+        let predictor = Predictor::with_swappable_dictionary(model, false).unwrap();
+        std::thread::scope(|scope| {
+            let predictor = &predictor;
+            scope.spawn(move || {
+                predictor.set_dictionary(vec![]).unwrap();
+            });
+        });
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(&[-22, 35, 38, 22, -32, 26, 28], sentence.boundary_scores());
+    }
+
+    fn create_test_user_dictionary() -> UserDictionary {
+        UserDictionary::new(
+            vec![
+                UserWord::new(
+                    "地球人".into(),
+                    vec![Some("名詞".into()), None, Some("ユーザー".into())],
+                )
+                .unwrap(),
+                UserWord::new("この".into(), vec![]).unwrap(),
+            ],
+            ConflictResolution::LongestMatch,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_predict_user_dictionary() {
+        let model = create_test_model();
+        let mut predictor = Predictor::new(model, false).unwrap();
+        assert!(predictor
+            .set_user_dictionary(Some(create_test_user_dictionary()))
+            .is_none());
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(&[-22, 54, 58, 43, -54, 68, 48], sentence.boundary_scores(),);
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary
+            ],
+            sentence.boundaries(),
+        );
+
+        assert!(predictor.set_user_dictionary(None).is_some());
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        assert_eq!(WordBoundary, sentence.boundaries()[5]);
+    }
+
+    #[test]
+    fn test_predict_user_dictionary_forced() {
+        let model = create_test_model();
+        let mut predictor = Predictor::new(model, false).unwrap();
+        predictor.set_user_dictionary(Some(create_test_user_dictionary()));
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        sentence.force_boundary(15).unwrap();
+        predictor.predict(&mut sentence);
+        // 地球人 conflicts with the constraint, so the predicted boundaries are kept.
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary,
+                WordBoundary
+            ],
+            sentence.boundaries(),
+        );
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_predict_user_dictionary_tags() {
+        let model = create_test_model();
+        let mut predictor = Predictor::new(model, true).unwrap();
+        predictor.set_user_dictionary(Some(create_test_user_dictionary()));
+        let data = predictor.serialize_to_vec().unwrap();
+        let (predictor, _) = unsafe { Predictor::deserialize_from_slice_unchecked(&data).unwrap() };
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        sentence.fill_tags();
+        assert_eq!(3, sentence.n_tags());
+        assert_eq!(
+            &[
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                Some(Cow::Borrowed("ヒト")),
+                None,
+            ],
+            &sentence.tags()[..9],
+        );
+        assert_eq!(
+            &[
+                Some(Cow::Borrowed("名詞")),
+                None,
+                Some(Cow::Borrowed("ユーザー")),
+                None,
+                None,
+                None,
+            ],
+            &sentence.tags()[18..],
+        );
+        assert_eq!(
+            vec![
+                vec![None, None, None],
+                vec![Some(0), Some(3), None],
+                vec![None, None, None],
+                vec![None, None, None],
+                vec![None, None, None],
+            ],
+            sentence
+                .iter_tokens()
+                .map(|token| token.tag_ids().to_vec())
+                .collect::<Vec<_>>(),
+        );
+    }
+
     #[cfg(feature = "tag-prediction")]
     #[test]
     fn test_serialization_tags() {
@@ -936,6 +2363,50 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_tag_ids() {
+        let model = create_test_model();
+        let predictor = Predictor::new(model, true).unwrap();
+        let data = predictor.serialize_to_vec().unwrap();
+        let (predictor, _) = unsafe { Predictor::deserialize_from_slice_unchecked(&data).unwrap() };
+        assert_eq!(Some(1), predictor.tag_id("接尾辞"));
+        assert_eq!(None, predictor.tag_id("動詞"));
+        assert_eq!(Some("チキュー"), predictor.tag_str(5));
+        assert_eq!(None, predictor.tag_str(7));
+
+        let mut sentence = Sentence::from_raw("この人は地球人だ").unwrap();
+        predictor.predict(&mut sentence);
+        sentence.fill_tags();
+        assert_eq!(
+            vec![
+                vec![None, None],
+                vec![Some(0), Some(3)],
+                vec![None, None],
+                vec![Some(0), Some(5)],
+                vec![Some(1), Some(2)],
+                vec![None, None],
+            ],
+            sentence
+                .iter_tokens()
+                .map(|token| token.tag_ids().to_vec())
+                .collect::<Vec<_>>(),
+        );
+
+        sentence.tags_mut()[3].take();
+        assert!(sentence
+            .iter_tokens()
+            .all(|token| token.tag_ids().is_empty()));
+    }
+
+    #[cfg(feature = "tag-prediction")]
+    #[test]
+    fn test_invalid_tag_id() {
+        let mut model = create_test_model();
+        model.0.tag_vocabulary.truncate(5);
+        assert!(Predictor::new(model, true).is_err());
+    }
+
     #[cfg(feature = "tag-prediction")]
     #[test]
     #[should_panic]
@@ -1,10 +1,15 @@
 use alloc::borrow::Cow;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
 
 use crate::errors::{Result, VaporettoError};
 use crate::predictor::Predictor;
 
+#[cfg(feature = "tag-prediction")]
+use crate::predictor::TagCandidate;
+
 /// Character type.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[repr(u8)]
@@ -81,17 +86,119 @@ pub enum CharacterBoundary {
     Unknown = 2,
 }
 
+/// Separators and an escape character of tokenized texts used by
+/// [`Sentence::from_tokenized_with_format()`] and related functions.
+///
+/// The default format uses a whitespace (`' '`) as a token separator, a slash (`'/'`) as a tag
+/// separator, and a back slash (`'\\'`) as an escape character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenizedFormat {
+    token_separator: char,
+    tag_separator: char,
+    escape: char,
+}
+
+impl Default for TokenizedFormat {
+    fn default() -> Self {
+        Self {
+            token_separator: ' ',
+            tag_separator: '/',
+            escape: '\\',
+        }
+    }
+}
+
+impl TokenizedFormat {
+    /// Creates a new format.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_separator` - A character inserted to each token boundary.
+    /// * `tag_separator` - A character inserted before each tag.
+    /// * `escape` - A character escaping the following character.
+    ///
+    /// # Errors
+    ///
+    /// If the characters are not distinct or contain NULL or `'0'`, an error variant will be
+    /// returned. `'0'` cannot be used because an escaped `0` represents an empty tag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{Sentence, TokenizedFormat};
+    ///
+    /// let format = TokenizedFormat::new('\t', '_', '\\').unwrap();
+    /// let s = Sentence::from_tokenized_with_format("火星_名詞\t猫_名詞", &format).unwrap();
+    /// assert_eq!("火星猫", s.as_raw_text());
+    ///
+    /// assert!(TokenizedFormat::new(' ', ' ', '\\').is_err());
+    /// assert!(TokenizedFormat::new(' ', '0', '\\').is_err());
+    /// ```
+    pub fn new(token_separator: char, tag_separator: char, escape: char) -> Result<Self> {
+        if token_separator == '\0' || tag_separator == '\0' || escape == '\0' {
+            return Err(VaporettoError::invalid_argument(
+                "format",
+                "must not contain NULL",
+            ));
+        }
+        if token_separator == '0' || tag_separator == '0' || escape == '0' {
+            return Err(VaporettoError::invalid_argument(
+                "format",
+                "must not contain '0', which represents an empty tag after the escape character",
+            ));
+        }
+        if token_separator == tag_separator || token_separator == escape || tag_separator == escape
+        {
+            return Err(VaporettoError::invalid_argument(
+                "format",
+                "separators and the escape character must be distinct",
+            ));
+        }
+        Ok(Self {
+            token_separator,
+            tag_separator,
+            escape,
+        })
+    }
+
+    /// Returns the token separator.
+    pub const fn token_separator(&self) -> char {
+        self.token_separator
+    }
+
+    /// Returns the tag separator.
+    pub const fn tag_separator(&self) -> char {
+        self.tag_separator
+    }
+
+    /// Returns the escape character.
+    pub const fn escape(&self) -> char {
+        self.escape
+    }
+
+    /// Returns `true` if the character must be escaped.
+    #[inline]
+    const fn is_special(&self, c: char) -> bool {
+        c == self.token_separator || c == self.tag_separator || c == self.escape
+    }
+}
+
 /// Sentence data containing boundary and tag annotations.
 pub struct Sentence<'a, 'b> {
     pub(crate) text: Cow<'a, str>,
     pub(crate) char_types: Vec<u8>,
     pub(crate) boundaries: Vec<CharacterBoundary>,
+    pub(crate) constraints: Vec<CharacterBoundary>,
+    preserve_whitespace: bool,
     pub(crate) boundary_scores: Vec<i32>,
     pub(crate) score_padding: usize,
     pub(crate) char_pma_states: Vec<u32>,
     pub(crate) type_pma_states: Vec<u32>,
     pub(crate) tags: Vec<Option<Cow<'b, str>>>,
     pub(crate) n_tags: usize,
+    // IDs of the tags filled by the predictor. Empty if the tags are not filled by it.
+    #[cfg(feature = "tag-prediction")]
+    pub(crate) tag_ids: Vec<Option<u32>>,
     predictor: Option<&'b Predictor>,
     str_to_char_pos: Vec<usize>,
     char_to_str_pos: Vec<usize>,
@@ -115,12 +222,16 @@ impl<'a, 'b> Default for Sentence<'a, 'b> {
             text: Cow::Borrowed(""),
             char_types: vec![],
             boundaries: vec![],
+            constraints: vec![],
+            preserve_whitespace: false,
             boundary_scores: vec![],
             score_padding: 0,
             char_pma_states: vec![],
             type_pma_states: vec![],
             tags: vec![],
             n_tags: 0,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
             predictor: None,
             str_to_char_pos: vec![],
             char_to_str_pos: vec![],
@@ -137,6 +248,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
         self.char_types.clear();
         self.char_types.push(CharacterType::Other as u8);
         self.boundaries.clear();
+        self.constraints.clear();
+        self.preserve_whitespace = false;
         self.boundary_scores.clear();
         self.score_padding = 0;
         self.char_pma_states.clear();
@@ -144,6 +257,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
         self.tags.clear();
         self.n_tags = 0;
         self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
         self.str_to_char_pos.clear();
         self.str_to_char_pos.push(0);
         self.str_to_char_pos.push(1);
@@ -226,6 +341,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             text,
             char_types,
             boundaries,
+            constraints: vec![],
+            preserve_whitespace: false,
             boundary_scores: vec![],
             score_padding: 0,
             char_pma_states: vec![],
@@ -233,6 +350,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             predictor: None,
             tags: vec![],
             n_tags: 0,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
             str_to_char_pos,
             char_to_str_pos,
         })
@@ -266,17 +385,23 @@ impl<'a, 'b> Sentence<'a, 'b> {
             self.set_default();
             return Err(e);
         }
+        self.constraints.clear();
+        self.preserve_whitespace = false;
         self.boundary_scores.clear();
         self.score_padding = 0;
         self.char_pma_states.clear();
         self.type_pma_states.clear();
         self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
         self.tags.clear();
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn parse_tokenized(
         tokenized_text: &str,
+        format: &TokenizedFormat,
         text: &mut String,
         char_types: &mut Vec<u8>,
         boundaries: &mut Vec<CharacterBoundary>,
@@ -297,18 +422,25 @@ impl<'a, 'b> Sentence<'a, 'b> {
         char_to_str_pos.clear();
         char_to_str_pos.push(0);
         let mut tag_str = None;
+        // `true` if the current tag field consists of an escaped `0` so far.
+        let mut empty_tag = false;
         let mut prev_boundary = false;
         let mut escape = false;
         let mut tags_tmp: Vec<Vec<_>> = vec![];
         let mut pos = 0;
         for c in tokenized_text.chars() {
             match (escape, c) {
+                // an empty tag
+                (true, '0') if !empty_tag && tag_str.as_ref().map_or(false, String::is_empty) => {
+                    escape = false;
+                    empty_tag = true;
+                }
                 // escape a following character
-                (false, '\\') => {
+                (false, c) if c == format.escape => {
                     escape = true;
                 }
                 // token boundary
-                (false, ' ') => {
+                (false, c) if c == format.token_separator => {
                     if text.is_empty() {
                         return Err(VaporettoError::invalid_argument(
                             "tokenized_text",
@@ -322,12 +454,16 @@ impl<'a, 'b> Sentence<'a, 'b> {
                         ));
                     }
                     if let Some(tag) = tag_str.take() {
-                        tags_tmp.last_mut().unwrap().push(tag);
+                        tags_tmp
+                            .last_mut()
+                            .unwrap()
+                            .push(Self::tag_field(tag, empty_tag));
                     }
+                    empty_tag = false;
                     prev_boundary = true;
                 }
                 // tag
-                (false, '/') => {
+                (false, c) if c == format.tag_separator => {
                     if text.is_empty() || prev_boundary {
                         return Err(VaporettoError::invalid_argument(
                             "tokenized_text",
@@ -335,8 +471,12 @@ impl<'a, 'b> Sentence<'a, 'b> {
                         ));
                     }
                     if let Some(tag) = tag_str.replace(String::new()) {
-                        tags_tmp.last_mut().unwrap().push(tag);
+                        tags_tmp
+                            .last_mut()
+                            .unwrap()
+                            .push(Self::tag_field(tag, empty_tag));
                     }
+                    empty_tag = false;
                 }
                 // escaped character or other character
                 (_, _) => {
@@ -348,6 +488,11 @@ impl<'a, 'b> Sentence<'a, 'b> {
                         ));
                     }
                     if let Some(tag) = tag_str.as_mut() {
+                        // An escaped `0` followed by other characters is just a `0`.
+                        if empty_tag {
+                            tag.push('0');
+                            empty_tag = false;
+                        }
                         tag.push(c);
                         continue;
                     }
@@ -378,19 +523,16 @@ impl<'a, 'b> Sentence<'a, 'b> {
             str_to_char_pos[pos] = i;
         }
         if let Some(tag) = tag_str.take() {
-            tags_tmp.last_mut().unwrap().push(tag);
+            tags_tmp
+                .last_mut()
+                .unwrap()
+                .push(Self::tag_field(tag, empty_tag));
         }
         let n_tags = tags_tmp.iter().fold(0, |acc, x| acc.max(x.len()));
         tags.clear();
         for ts in tags_tmp {
             let n_fill_none = n_tags - ts.len();
-            for t in ts {
-                if t.is_empty() {
-                    tags.push(None);
-                } else {
-                    tags.push(Some(Cow::Owned(t)));
-                }
-            }
+            tags.extend(ts);
             for _ in 0..n_fill_none {
                 tags.push(None);
             }
@@ -398,12 +540,28 @@ impl<'a, 'b> Sentence<'a, 'b> {
         Ok(())
     }
 
+    /// Converts a tag field of the tokenized format into a tag. An empty field is a missing tag,
+    /// and a field consisting of an escaped `0` is an empty tag.
+    #[inline]
+    fn tag_field(tag: String, empty_tag: bool) -> Option<Cow<'b, str>> {
+        if empty_tag {
+            Some(Cow::Borrowed(""))
+        } else if tag.is_empty() {
+            None
+        } else {
+            Some(Cow::Owned(tag))
+        }
+    }
+
     /// Creates a new [`Sentence`] from a tokenized text.
     ///
     /// A tokenized text must be annotated by the following rules:
     ///   - A whitespace (`' '`) is inserted to each token boundary.
     ///   - If necessary, multiple tags following each slash (`'/'`) can be added to each token.
     ///   - Each character following a back slash (`'\\'`) is escaped.
+    ///   - An empty tag field represents a missing tag (`None`), and a tag field consisting of
+    ///     `\\0` represents an empty tag (`Some("")`).
+    ///   - Tokens with fewer tags than others are padded with missing tags.
     ///
     /// # Errors
     ///
@@ -425,6 +583,32 @@ impl<'a, 'b> Sentence<'a, 'b> {
     /// assert!(s.is_err());
     /// ```
     pub fn from_tokenized(tokenized_text: &str) -> Result<Self> {
+        Self::from_tokenized_with_format(tokenized_text, &TokenizedFormat::default())
+    }
+
+    /// Creates a new [`Sentence`] from a tokenized text in the given format.
+    ///
+    /// This function is the same as [`Sentence::from_tokenized()`] except that separators and
+    /// the escape character are specified by `format`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error variant when the given text is empty, starts/ends with a
+    /// token separator, or contains consecutive token separators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{Sentence, TokenizedFormat};
+    ///
+    /// let format = TokenizedFormat::new('|', '_', '\\').unwrap();
+    /// let s = Sentence::from_tokenized_with_format("まぁ_副詞|良い|だろ\\|う_助動詞", &format);
+    /// assert_eq!("まぁ良いだろ|う", s.unwrap().as_raw_text());
+    /// ```
+    pub fn from_tokenized_with_format(
+        tokenized_text: &str,
+        format: &TokenizedFormat,
+    ) -> Result<Self> {
         let mut text = String::new();
         let mut char_types = vec![];
         let mut boundaries = vec![];
@@ -433,6 +617,7 @@ impl<'a, 'b> Sentence<'a, 'b> {
         let mut tags = vec![];
         Self::parse_tokenized(
             tokenized_text,
+            format,
             &mut text,
             &mut char_types,
             &mut boundaries,
@@ -445,6 +630,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             text: Cow::Owned(text),
             char_types,
             boundaries,
+            constraints: vec![],
+            preserve_whitespace: false,
             boundary_scores: vec![],
             score_padding: 0,
             char_pma_states: vec![],
@@ -452,6 +639,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             predictor: None,
             tags,
             n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
             str_to_char_pos,
             char_to_str_pos,
         })
@@ -483,8 +672,26 @@ impl<'a, 'b> Sentence<'a, 'b> {
     /// assert_eq!("まぁ良いだろう", s.as_raw_text());
     /// ```
     pub fn update_tokenized(&mut self, tokenized_text: &str) -> Result<()> {
+        self.update_tokenized_with_format(tokenized_text, &TokenizedFormat::default())
+    }
+
+    /// Updates the [`Sentence`] using a tokenized text in the given format.
+    ///
+    /// This function is the same as [`Sentence::update_tokenized()`] except that separators and
+    /// the escape character are specified by `format`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error variant when the given text is empty, starts/ends with a
+    /// token separator, or contains consecutive token separators.
+    pub fn update_tokenized_with_format(
+        &mut self,
+        tokenized_text: &str,
+        format: &TokenizedFormat,
+    ) -> Result<()> {
         if let Err(e) = Self::parse_tokenized(
             tokenized_text,
+            format,
             self.text.to_mut(),
             &mut self.char_types,
             &mut self.boundaries,
@@ -495,11 +702,15 @@ impl<'a, 'b> Sentence<'a, 'b> {
             self.set_default();
             return Err(e);
         }
+        self.constraints.clear();
+        self.preserve_whitespace = false;
         self.boundary_scores.clear();
         self.score_padding = 0;
         self.char_pma_states.clear();
         self.type_pma_states.clear();
         self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
         self.n_tags = self.tags.len() / self.char_types.len();
         Ok(())
     }
@@ -682,6 +893,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             text: Cow::Owned(text),
             char_types,
             boundaries,
+            constraints: vec![],
+            preserve_whitespace: false,
             boundary_scores: vec![],
             score_padding: 0,
             char_pma_states: vec![],
@@ -689,6 +902,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
             predictor: None,
             tags,
             n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
             str_to_char_pos,
             char_to_str_pos,
         })
@@ -748,132 +963,607 @@ impl<'a, 'b> Sentence<'a, 'b> {
             self.set_default();
             return Err(e);
         }
+        self.constraints.clear();
+        self.preserve_whitespace = false;
         self.boundary_scores.clear();
         self.score_padding = 0;
         self.char_pma_states.clear();
         self.type_pma_states.clear();
         self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
         self.n_tags = self.tags.len() / self.char_types.len();
         Ok(())
     }
 
-    /// Gets a text without any annotation.
+    /// Builds a sentence from pairs of a surface and tags. Returns an error message if the tokens
+    /// are invalid.
+    fn parse_tokens(
+        tokens: &[(&str, Vec<&str>)],
+        text: &mut String,
+        char_types: &mut Vec<u8>,
+        boundaries: &mut Vec<CharacterBoundary>,
+        str_to_char_pos: &mut Vec<usize>,
+        char_to_str_pos: &mut Vec<usize>,
+        tags: &mut Vec<Option<Cow<'b, str>>>,
+    ) -> core::result::Result<(), &'static str> {
+        if tokens.is_empty() {
+            return Err("must contain at least one token");
+        }
+        text.clear();
+        char_types.clear();
+        boundaries.clear();
+        str_to_char_pos.clear();
+        char_to_str_pos.clear();
+        char_to_str_pos.push(0);
+        let n_tags = tokens.iter().fold(0, |acc, (_, ts)| acc.max(ts.len()));
+        tags.clear();
+        let mut pos = 0;
+        for (surface, ts) in tokens {
+            if surface.is_empty() {
+                return Err("must not contain empty tokens");
+            }
+            if surface.contains('\0') {
+                return Err("must not contain NULL");
+            }
+            for (i, c) in surface.chars().enumerate() {
+                if !text.is_empty() {
+                    boundaries.push(if i == 0 {
+                        CharacterBoundary::WordBoundary
+                    } else {
+                        CharacterBoundary::NotWordBoundary
+                    });
+                }
+                text.push(c);
+                char_types.push(CharacterType::get_type(c) as u8);
+                pos += c.len_utf8();
+                char_to_str_pos.push(pos);
+            }
+            let n_chars = char_to_str_pos.len() - 1;
+            tags.resize((n_chars - 1) * n_tags, None);
+            for t in ts {
+                if t.is_empty() {
+                    tags.push(None);
+                } else {
+                    tags.push(Some(Cow::Owned((*t).into())));
+                }
+            }
+            tags.resize(n_chars * n_tags, None);
+        }
+        str_to_char_pos.resize(pos + 1, 0);
+        for (i, &pos) in char_to_str_pos.iter().enumerate() {
+            str_to_char_pos[pos] = i;
+        }
+        Ok(())
+    }
+
+    fn parse_conllu(
+        conllu_text: &str,
+        text: &mut String,
+        char_types: &mut Vec<u8>,
+        boundaries: &mut Vec<CharacterBoundary>,
+        str_to_char_pos: &mut Vec<usize>,
+        char_to_str_pos: &mut Vec<usize>,
+        tags: &mut Vec<Option<Cow<'b, str>>>,
+    ) -> Result<bool> {
+        let mut tokens = vec![];
+        let mut space_before = false;
+        let mut contains_space = false;
+        // The last ID of the current multiword token and whether the token is followed by a space.
+        let mut multiword: Option<(usize, bool)> = None;
+        for line in conllu_text.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cols: Vec<_> = line.split('\t').collect();
+            if cols.len() != 10 {
+                return Err(VaporettoError::invalid_argument(
+                    "conllu_text",
+                    "each token line must contain 10 fields",
+                ));
+            }
+            let space_after = !cols[9].split('|').any(|f| f == "SpaceAfter=No");
+            // Skips multiword tokens and empty nodes because their surfaces are not part of the
+            // text or duplicate other tokens. SpaceAfter of a multiword token is applied to its
+            // last word.
+            if let Some((_, end)) = cols[0].split_once('-') {
+                multiword = end.parse().ok().map(|end| (end, space_after));
+                continue;
+            }
+            if cols[0].contains('.') {
+                continue;
+            }
+            let id = cols[0].parse().ok();
+            let space_after = match multiword {
+                Some((end, multiword_space_after)) if id.map_or(false, |id| id <= end) => {
+                    id == Some(end) && multiword_space_after
+                }
+                _ => space_after,
+            };
+            if space_before {
+                tokens.push((" ", vec![]));
+                contains_space = true;
+            }
+            let ts = cols[3..5]
+                .iter()
+                .map(|&t| if t == "_" { "" } else { t })
+                .collect();
+            tokens.push((cols[1], ts));
+            space_before = space_after;
+        }
+        Self::parse_tokens(
+            &tokens,
+            text,
+            char_types,
+            boundaries,
+            str_to_char_pos,
+            char_to_str_pos,
+            tags,
+        )
+        .map_err(|msg| VaporettoError::invalid_argument("conllu_text", msg))?;
+        Ok(contains_space)
+    }
+
+    /// Creates a new [`Sentence`] from a sentence in the CoNLL-U format.
     ///
-    /// # Examples
+    /// The text is the concatenation of the `FORM` field of each word, and the `UPOS` and `XPOS`
+    /// fields are stored as the first and second tags. A space is inserted after each word except
+    /// the last one unless its `MISC` field contains `SpaceAfter=No`, which is read from the
+    /// multiword token for its last word. If a space is inserted, the whitespace-preserving mode is
+    /// enabled by [`Sentence::preserve_whitespace()`], so spaces are not tokens. Comment lines,
+    /// multiword tokens, and empty nodes are ignored, and `_` is treated as an empty tag.
     ///
-    /// ```
-    /// use vaporetto::Sentence;
+    /// # Errors
     ///
-    /// let s = Sentence::from_tokenized("まぁ/副詞 良い/形容詞 だろう/助動詞").unwrap();
-    /// assert_eq!("まぁ良いだろう", s.as_raw_text());
-    /// ```
-    #[inline]
-    pub fn as_raw_text(&self) -> &str {
-        &self.text
-    }
-
-    /// Returns an iterator of tokens. Tokens adjacent to [`CharacterBoundary::Unknown`] will be
-    /// skipped.
+    /// This function will return an error variant when the text contains no words or a token line
+    /// does not have 10 fields.
     ///
     /// # Examples
     ///
     /// ```
     /// use vaporetto::Sentence;
     ///
-    /// let s = Sentence::from_partial_annotation("ま-ぁ|社-長|は|火-星 猫|だ").unwrap();
-    /// let mut it = s.iter_tokens();
-    ///
-    /// let token = it.next().unwrap();
-    /// assert_eq!("まぁ", token.surface());
-    /// assert_eq!(0, token.start());
-    /// assert_eq!(2, token.end());
-    ///
-    /// let token = it.next().unwrap();
-    /// assert_eq!("社長", token.surface());
-    /// assert_eq!(2, token.start());
-    /// assert_eq!(4, token.end());
-    ///
-    /// let token = it.next().unwrap();
-    /// assert_eq!("は", token.surface());
-    /// assert_eq!(4, token.start());
-    /// assert_eq!(5, token.end());
-    ///
-    /// let token = it.next().unwrap();
-    /// assert_eq!("だ", token.surface());
-    /// assert_eq!(8, token.start());
-    /// assert_eq!(9, token.end());
+    /// let s = Sentence::from_conllu(
+    ///     "# text = 火星猫だ\n\
+    ///      1\t火星\t火星\tNOUN\t名詞\t_\t_\t_\t_\tSpaceAfter=No\n\
+    ///      2\t猫\t猫\tNOUN\t名詞\t_\t_\t_\t_\tSpaceAfter=No\n\
+    ///      3\tだ\tだ\tAUX\t_\t_\t_\t_\t_\t_\n"
+    /// ).unwrap();
     ///
-    /// assert!(it.next().is_none());
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星/NOUN/名詞 猫/NOUN/名詞 だ/AUX", buf);
     /// ```
-    pub const fn iter_tokens(&'a self) -> TokenIterator<'a, 'b> {
-        TokenIterator {
-            token: Token {
-                sentence: self,
-                start: 0,
-                end: 0,
-            },
+    pub fn from_conllu(conllu_text: &str) -> Result<Self> {
+        let mut text = String::new();
+        let mut char_types = vec![];
+        let mut boundaries = vec![];
+        let mut str_to_char_pos = vec![];
+        let mut char_to_str_pos = vec![];
+        let mut tags = vec![];
+        let contains_space = Self::parse_conllu(
+            conllu_text,
+            &mut text,
+            &mut char_types,
+            &mut boundaries,
+            &mut str_to_char_pos,
+            &mut char_to_str_pos,
+            &mut tags,
+        )?;
+        let n_tags = tags.len() / char_types.len();
+        let mut s = Self {
+            text: Cow::Owned(text),
+            char_types,
+            boundaries,
+            constraints: vec![],
+            preserve_whitespace: false,
+            boundary_scores: vec![],
+            score_padding: 0,
+            char_pma_states: vec![],
+            type_pma_states: vec![],
+            predictor: None,
+            tags,
+            n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
+            str_to_char_pos,
+            char_to_str_pos,
+        };
+        if contains_space {
+            s.preserve_whitespace()?;
         }
+        Ok(s)
     }
 
-    /// Writes a tokenized text. Tokens adjacent to [`CharacterBoundary::Unknown`] will be skipped.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vaporetto::Sentence;
+    /// Updates the [`Sentence`] using a sentence in the CoNLL-U format.
     ///
-    /// let mut buf = String::new();
+    /// See [`Sentence::from_conllu()`] for the details of the format.
     ///
-    /// let s = Sentence::from_partial_annotation(
-    ///     "ま-ぁ/名詞|社-長/名詞|は/助詞|火-星/名詞|猫/名詞|だ/助動詞"
-    /// ).unwrap();
-    /// s.write_tokenized_text(&mut buf);
-    /// assert_eq!("まぁ/名詞 社長/名詞 は/助詞 火星/名詞 猫/名詞 だ/助動詞", buf);
+    /// # Errors
     ///
-    /// let s = Sentence::from_partial_annotation(
-    ///     "ま-ぁ/名詞|社-長/名詞|は/助詞|火-星 猫|だ/助動詞"
-    /// ).unwrap();
-    /// s.write_tokenized_text(&mut buf);
-    /// assert_eq!("まぁ/名詞 社長/名詞 は/助詞 だ/助動詞", buf);
-    /// ```
-    pub fn write_tokenized_text(&self, buf: &mut String) {
-        buf.clear();
-        // `buf` always consists of a valid UTF-8 sequence because
-        // `Token::surface` and `Token::tags` return values in `str`.
-        unsafe {
-            let buf = buf.as_mut_vec();
-            for token in self.iter_tokens() {
-                if !buf.is_empty() {
-                    buf.push(b' ');
-                }
-                for &b in token.surface().as_bytes() {
-                    match b {
-                        b' ' | b'\\' | b'/' => {
-                            buf.push(b'\\');
-                        }
-                        _ => (),
-                    }
-                    buf.push(b);
-                }
-                let ts = token.tags();
-                for tag in &ts[..ts.iter().rposition(|x| x.is_some()).map_or(0, |x| x + 1)] {
-                    buf.push(b'/');
-                    if let Some(tag) = tag {
-                        for &b in tag.as_bytes() {
-                            match b {
-                                b' ' | b'\\' | b'/' => {
-                                    buf.push(b'\\');
-                                }
-                                _ => (),
+    /// This function will return an error variant when the text contains no words or a token line
+    /// does not have 10 fields.
+    pub fn update_conllu(&mut self, conllu_text: &str) -> Result<()> {
+        let contains_space = match Self::parse_conllu(
+            conllu_text,
+            self.text.to_mut(),
+            &mut self.char_types,
+            &mut self.boundaries,
+            &mut self.str_to_char_pos,
+            &mut self.char_to_str_pos,
+            &mut self.tags,
+        ) {
+            Ok(contains_space) => contains_space,
+            Err(e) => {
+                self.set_default();
+                return Err(e);
+            }
+        };
+        self.constraints.clear();
+        self.preserve_whitespace = false;
+        self.boundary_scores.clear();
+        self.score_padding = 0;
+        self.char_pma_states.clear();
+        self.type_pma_states.clear();
+        self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
+        self.n_tags = self.tags.len() / self.char_types.len();
+        if contains_space {
+            self.preserve_whitespace()?;
+        }
+        Ok(())
+    }
+
+    fn parse_tsv(
+        tsv_text: &str,
+        text: &mut String,
+        char_types: &mut Vec<u8>,
+        boundaries: &mut Vec<CharacterBoundary>,
+        str_to_char_pos: &mut Vec<usize>,
+        char_to_str_pos: &mut Vec<usize>,
+        tags: &mut Vec<Option<Cow<'b, str>>>,
+    ) -> Result<()> {
+        let mut tokens = vec![];
+        for line in tsv_text.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let mut cols = line.split('\t');
+            let surface = cols.next().unwrap();
+            tokens.push((surface, cols.collect()));
+        }
+        Self::parse_tokens(
+            &tokens,
+            text,
+            char_types,
+            boundaries,
+            str_to_char_pos,
+            char_to_str_pos,
+            tags,
+        )
+        .map_err(|msg| VaporettoError::invalid_argument("tsv_text", msg))
+    }
+
+    /// Creates a new [`Sentence`] from a text in the TSV format.
+    ///
+    /// Each line contains a token followed by its tags separated by tabs. Empty fields are
+    /// treated as empty tags, and empty lines are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error variant when the text contains no tokens or a line
+    /// starts with a tab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let s = Sentence::from_tsv("火星\t名詞\tカセー\n猫\t\tネコ\nだ\n").unwrap();
+    ///
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星/名詞/カセー 猫//ネコ だ", buf);
+    /// ```
+    pub fn from_tsv(tsv_text: &str) -> Result<Self> {
+        let mut text = String::new();
+        let mut char_types = vec![];
+        let mut boundaries = vec![];
+        let mut str_to_char_pos = vec![];
+        let mut char_to_str_pos = vec![];
+        let mut tags = vec![];
+        Self::parse_tsv(
+            tsv_text,
+            &mut text,
+            &mut char_types,
+            &mut boundaries,
+            &mut str_to_char_pos,
+            &mut char_to_str_pos,
+            &mut tags,
+        )?;
+        let n_tags = tags.len() / char_types.len();
+        Ok(Self {
+            text: Cow::Owned(text),
+            char_types,
+            boundaries,
+            constraints: vec![],
+            preserve_whitespace: false,
+            boundary_scores: vec![],
+            score_padding: 0,
+            char_pma_states: vec![],
+            type_pma_states: vec![],
+            predictor: None,
+            tags,
+            n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: vec![],
+            str_to_char_pos,
+            char_to_str_pos,
+        })
+    }
+
+    /// Updates the [`Sentence`] using a text in the TSV format.
+    ///
+    /// See [`Sentence::from_tsv()`] for the details of the format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error variant when the text contains no tokens or a line
+    /// starts with a tab.
+    pub fn update_tsv(&mut self, tsv_text: &str) -> Result<()> {
+        if let Err(e) = Self::parse_tsv(
+            tsv_text,
+            self.text.to_mut(),
+            &mut self.char_types,
+            &mut self.boundaries,
+            &mut self.str_to_char_pos,
+            &mut self.char_to_str_pos,
+            &mut self.tags,
+        ) {
+            self.set_default();
+            return Err(e);
+        }
+        self.constraints.clear();
+        self.preserve_whitespace = false;
+        self.boundary_scores.clear();
+        self.score_padding = 0;
+        self.char_pma_states.clear();
+        self.type_pma_states.clear();
+        self.predictor.take();
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
+        self.n_tags = self.tags.len() / self.char_types.len();
+        Ok(())
+    }
+
+    /// Gets a text without any annotation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let s = Sentence::from_tokenized("まぁ/副詞 良い/形容詞 だろう/助動詞").unwrap();
+    /// assert_eq!("まぁ良いだろう", s.as_raw_text());
+    /// ```
+    #[inline]
+    pub fn as_raw_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns an iterator of tokens. Tokens adjacent to [`CharacterBoundary::Unknown`] will be
+    /// skipped. If [`Sentence::preserve_whitespace()`] is called, tokens consisting of whitespace
+    /// will also be skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let s = Sentence::from_partial_annotation("ま-ぁ|社-長|は|火-星 猫|だ").unwrap();
+    /// let mut it = s.iter_tokens();
+    ///
+    /// let token = it.next().unwrap();
+    /// assert_eq!("まぁ", token.surface());
+    /// assert_eq!(0, token.start());
+    /// assert_eq!(2, token.end());
+    ///
+    /// let token = it.next().unwrap();
+    /// assert_eq!("社長", token.surface());
+    /// assert_eq!(2, token.start());
+    /// assert_eq!(4, token.end());
+    ///
+    /// let token = it.next().unwrap();
+    /// assert_eq!("は", token.surface());
+    /// assert_eq!(4, token.start());
+    /// assert_eq!(5, token.end());
+    ///
+    /// let token = it.next().unwrap();
+    /// assert_eq!("だ", token.surface());
+    /// assert_eq!(8, token.start());
+    /// assert_eq!(9, token.end());
+    ///
+    /// assert!(it.next().is_none());
+    /// ```
+    pub const fn iter_tokens(&'a self) -> TokenIterator<'a, 'b> {
+        TokenIterator {
+            token: Token {
+                sentence: self,
+                start: 0,
+                end: 0,
+            },
+        }
+    }
+
+    /// Writes a tokenized text. Tokens adjacent to [`CharacterBoundary::Unknown`] will be skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_partial_annotation(
+    ///     "ま-ぁ/名詞|社-長/名詞|は/助詞|火-星/名詞|猫/名詞|だ/助動詞"
+    /// ).unwrap();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("まぁ/名詞 社長/名詞 は/助詞 火星/名詞 猫/名詞 だ/助動詞", buf);
+    ///
+    /// let s = Sentence::from_partial_annotation(
+    ///     "ま-ぁ/名詞|社-長/名詞|は/助詞|火-星 猫|だ/助動詞"
+    /// ).unwrap();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("まぁ/名詞 社長/名詞 は/助詞 だ/助動詞", buf);
+    /// ```
+    pub fn write_tokenized_text(&self, buf: &mut String) {
+        self.write_tokenized_text_with_format(buf, &TokenizedFormat::default());
+    }
+
+    /// Writes a tokenized text in the given format. Tokens adjacent to
+    /// [`CharacterBoundary::Unknown`] will be skipped.
+    ///
+    /// Tags are written in the lossless encoding described in [`Sentence::from_tokenized()`], so
+    /// the sentence can be restored by [`Sentence::from_tokenized_with_format()`] unless tokens
+    /// are skipped. Missing tags at the end of each token are omitted, but if no token has the
+    /// last tag, the last token is padded with empty tag fields to keep [`Sentence::n_tags()`].
+    /// A sentence without any tags is written without tag fields, so its number of tags is
+    /// restored as 0. This keeps outputs of sentences whose tags are reset by
+    /// [`Sentence::reset_tags()`] or not predicted free of tag separators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{Sentence, TokenizedFormat};
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_tokenized("まぁ/副詞 良い だろ|う/助動詞").unwrap();
+    /// let format = TokenizedFormat::new('|', '_', '\\').unwrap();
+    /// s.write_tokenized_text_with_format(&mut buf, &format);
+    /// assert_eq!("まぁ_副詞|良い|だろ\\|う_助動詞", buf);
+    /// ```
+    pub fn write_tokenized_text_with_format(&self, buf: &mut String, format: &TokenizedFormat) {
+        buf.clear();
+        // If no token has the last tag, the last token is padded with missing tags to keep the
+        // number of tags. Sentences without tags are written without padding, so their number of
+        // tags is not kept.
+        let pad_last = self.tags.iter().any(Option::is_some)
+            && !self
+                .iter_tokens()
+                .any(|token| matches!(token.tags().last(), Some(Some(_))));
+        let mut it = self.iter_tokens().peekable();
+        while let Some(token) = it.next() {
+            if !buf.is_empty() {
+                buf.push(format.token_separator);
+            }
+            for c in token.surface().chars() {
+                if format.is_special(c) {
+                    buf.push(format.escape);
+                }
+                buf.push(c);
+            }
+            let ts = token.tags();
+            let n_tags = if pad_last && it.peek().is_none() {
+                ts.len()
+            } else {
+                ts.iter().rposition(|x| x.is_some()).map_or(0, |x| x + 1)
+            };
+            for tag in &ts[..n_tags] {
+                buf.push(format.tag_separator);
+                match tag.as_deref() {
+                    Some("") => {
+                        buf.push(format.escape);
+                        buf.push('0');
+                    }
+                    Some(tag) => {
+                        for c in tag.chars() {
+                            if format.is_special(c) {
+                                buf.push(format.escape);
                             }
-                            buf.push(b);
+                            buf.push(c);
                         }
                     }
+                    None => {}
                 }
             }
         }
     }
 
+    /// Writes a natural text rebuilt from tokens. Tokens adjacent to
+    /// [`CharacterBoundary::Unknown`] will be skipped.
+    ///
+    /// If the whitespace-preserving mode is enabled by [`Sentence::preserve_whitespace()`], the
+    /// original whitespace between tokens is restored, so the text is reconstructed exactly
+    /// unless tokens are skipped. Otherwise, a space is inserted between two tokens unless either
+    /// side is a Japanese character, a full-width symbol, or a punctuation mark that does not
+    /// take a space on that side, such as a comma or a bracket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CharacterBoundary, Sentence};
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_tokenized("Rust で 「 Hello , world ! 」 と 書く").unwrap();
+    /// s.detokenize(&mut buf);
+    /// assert_eq!("Rustで「Hello, world!」と書く", buf);
+    ///
+    /// let mut s = Sentence::from_raw("Hello,  world !").unwrap();
+    /// s.boundaries_mut().fill(CharacterBoundary::WordBoundary);
+    /// s.preserve_whitespace().unwrap();
+    /// s.detokenize(&mut buf);
+    /// assert_eq!("Hello,  world !", buf);
+    /// ```
+    pub fn detokenize(&self, buf: &mut String) {
+        buf.clear();
+        let mut prev_end = 0;
+        for token in self.iter_tokens() {
+            let gap = self.text_substring(prev_end, token.start());
+            let surface = token.surface();
+            if self.preserve_whitespace && gap.chars().all(char::is_whitespace) {
+                buf.push_str(gap);
+            } else if let (Some(left), Some(right)) =
+                (buf.chars().next_back(), surface.chars().next())
+            {
+                if Self::needs_space(left, right) {
+                    buf.push(' ');
+                }
+            }
+            buf.push_str(surface);
+            prev_end = token.end();
+        }
+        if self.preserve_whitespace {
+            let gap = self.text_substring(prev_end, self.len());
+            if gap.chars().all(char::is_whitespace) {
+                buf.push_str(gap);
+            }
+        }
+    }
+
+    /// Returns `true` if a space should be inserted between the given characters when
+    /// detokenizing.
+    fn needs_space(left: char, right: char) -> bool {
+        let is_cjk = |c: char| {
+            matches!(
+                CharacterType::get_type(c),
+                CharacterType::Hiragana | CharacterType::Katakana | CharacterType::Kanji
+            ) || matches!(u32::from(c), 0x3000..=0x303F)
+                || (matches!(u32::from(c), 0xFF00..=0xFFEF) && !c.is_alphanumeric())
+        };
+        !(left.is_whitespace()
+            || right.is_whitespace()
+            || is_cjk(left)
+            || is_cjk(right)
+            || matches!(left, '(' | '[' | '{' | '“' | '‘')
+            || matches!(
+                right,
+                ',' | '.' | '!' | '?' | ':' | ';' | '%' | ')' | ']' | '}' | '”' | '’' | '\''
+            ))
+    }
+
     /// Writes a text with partial annotations.
     ///
     /// # Examples
@@ -932,6 +1622,162 @@ impl<'a, 'b> Sentence<'a, 'b> {
         }
     }
 
+    /// Writes the sentence in the CoNLL-U format.
+    ///
+    /// The raw text is written as a `text` comment, and the first and second tags of each token
+    /// are written in the `UPOS` and `XPOS` fields. `SpaceAfter=No` is written to all tokens
+    /// except the last one since tokens are not separated by spaces in the raw text. If the
+    /// whitespace-preserving mode is enabled by [`Sentence::preserve_whitespace()`], it is only
+    /// written to tokens immediately followed by the next token. The output ends with an empty
+    /// line, so outputs of multiple sentences can be concatenated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_tokenized("火星/NOUN 猫/NOUN だ/AUX/助動詞").unwrap();
+    /// s.write_conllu(&mut buf);
+    /// assert_eq!(
+    ///     "# text = 火星猫だ\n\
+    ///      1\t火星\t_\tNOUN\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+    ///      2\t猫\t_\tNOUN\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+    ///      3\tだ\t_\tAUX\t助動詞\t_\t_\t_\t_\t_\n\
+    ///      \n",
+    ///     buf,
+    /// );
+    /// ```
+    pub fn write_conllu(&self, buf: &mut String) {
+        buf.clear();
+        buf.push_str("# text = ");
+        buf.push_str(&self.text);
+        buf.push('\n');
+        let mut it = self.iter_tokens().enumerate().peekable();
+        while let Some((i, token)) = it.next() {
+            let ts = token.tags();
+            let upos = ts.first().and_then(|t| t.as_deref()).unwrap_or("_");
+            let xpos = ts.get(1).and_then(|t| t.as_deref()).unwrap_or("_");
+            let space_after = it.peek().map_or(true, |(_, next)| {
+                self.preserve_whitespace && next.start() != token.end()
+            });
+            let misc = if space_after { "_" } else { "SpaceAfter=No" };
+            writeln!(
+                buf,
+                "{}\t{}\t_\t{upos}\t{xpos}\t_\t_\t_\t_\t{misc}",
+                i + 1,
+                token.surface(),
+            )
+            .unwrap();
+        }
+        buf.push('\n');
+    }
+
+    /// Writes the sentence in the TSV format.
+    ///
+    /// Each line contains a token followed by its tags separated by tabs. `None` tags are written
+    /// as empty fields, and trailing `None` tags are omitted. The output ends with an empty line,
+    /// so outputs of multiple sentences can be concatenated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_tokenized("火星/名詞/カセー 猫//ネコ だ").unwrap();
+    /// s.write_tsv(&mut buf);
+    /// assert_eq!("火星\t名詞\tカセー\n猫\t\tネコ\nだ\n\n", buf);
+    /// ```
+    pub fn write_tsv(&self, buf: &mut String) {
+        buf.clear();
+        for token in self.iter_tokens() {
+            buf.push_str(token.surface());
+            let ts = token.tags();
+            for tag in &ts[..ts.iter().rposition(|x| x.is_some()).map_or(0, |x| x + 1)] {
+                buf.push('\t');
+                if let Some(tag) = tag {
+                    buf.push_str(tag);
+                }
+            }
+            buf.push('\n');
+        }
+        buf.push('\n');
+    }
+
+    /// Writes the sentence in the MeCab format.
+    ///
+    /// Each line contains a token and its features separated by a tab, and the output ends with
+    /// `EOS`. Each element of `feature_slots` specifies the index of the tag written in the
+    /// corresponding feature column, and `None` or a missing tag is written as `*`. If
+    /// `feature_slots` is empty, all tags are written in order. Features containing commas or
+    /// double quotes are quoted in the same way as MeCab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut buf = String::new();
+    ///
+    /// let s = Sentence::from_tokenized("火星/名詞/カセー 猫/名詞 だ/助動詞/ダ").unwrap();
+    /// s.write_mecab_text(&mut buf, &[Some(0), None, Some(1)]);
+    /// assert_eq!(
+    ///     "火星\t名詞,*,カセー\n猫\t名詞,*,*\nだ\t助動詞,*,ダ\nEOS\n",
+    ///     buf,
+    /// );
+    ///
+    /// s.write_mecab_text(&mut buf, &[]);
+    /// assert_eq!("火星\t名詞,カセー\n猫\t名詞,*\nだ\t助動詞,ダ\nEOS\n", buf);
+    /// ```
+    pub fn write_mecab_text(&self, buf: &mut String, feature_slots: &[Option<usize>]) {
+        fn push_feature(buf: &mut String, feature: Option<&str>) {
+            match feature {
+                Some(f) if f.contains([',', '"']) => {
+                    buf.push('"');
+                    for c in f.chars() {
+                        if c == '"' {
+                            buf.push('"');
+                        }
+                        buf.push(c);
+                    }
+                    buf.push('"');
+                }
+                Some(f) => buf.push_str(f),
+                None => buf.push('*'),
+            }
+        }
+
+        buf.clear();
+        for token in self.iter_tokens() {
+            buf.push_str(token.surface());
+            buf.push('\t');
+            let ts = token.tags();
+            if feature_slots.is_empty() {
+                for (i, tag) in ts.iter().enumerate() {
+                    if i != 0 {
+                        buf.push(',');
+                    }
+                    push_feature(buf, tag.as_deref());
+                }
+            } else {
+                for (i, slot) in feature_slots.iter().enumerate() {
+                    if i != 0 {
+                        buf.push(',');
+                    }
+                    let tag = slot
+                        .and_then(|slot| ts.get(slot))
+                        .and_then(|t| t.as_deref());
+                    push_feature(buf, tag);
+                }
+            }
+            buf.push('\n');
+        }
+        buf.push_str("EOS\n");
+    }
+
     /// Removes tag information and updates the number of tags.
     ///
     /// # Examples
@@ -950,78 +1796,345 @@ impl<'a, 'b> Sentence<'a, 'b> {
     /// assert_eq!(1, s.n_tags());
     /// assert_eq!(8, s.tags().len());
     /// s.write_tokenized_text(&mut buf);
-    /// assert_eq!("火星 に 行き まし た", buf);
+    /// assert_eq!("火星 に 行き まし た", buf);
+    /// ```
+    #[inline]
+    pub fn reset_tags(&mut self, n_tags: usize) {
+        self.tags.clear();
+        self.tags.resize(n_tags * self.len(), None);
+        self.n_tags = n_tags;
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
+    }
+
+    /// Returns a slice of character types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CharacterType, Sentence};
+    ///
+    /// let s = Sentence::from_tokenized("火星 に 行き まし た").unwrap();
+    /// assert_eq!(&[
+    ///     CharacterType::Kanji as u8,
+    ///     CharacterType::Kanji as u8,
+    ///     CharacterType::Hiragana as u8,
+    ///     CharacterType::Kanji as u8,
+    ///     CharacterType::Hiragana as u8,
+    ///     CharacterType::Hiragana as u8,
+    ///     CharacterType::Hiragana as u8,
+    ///     CharacterType::Hiragana as u8,
+    /// ], s.char_types());
+    /// ```
+    #[inline]
+    pub fn char_types(&self) -> &[u8] {
+        &self.char_types
+    }
+
+    /// Returns a slice of boundary types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CharacterBoundary, Sentence};
+    ///
+    /// let s = Sentence::from_partial_annotation("火-星|に|行-き|ま-し た").unwrap();
+    /// assert_eq!(&[
+    ///     CharacterBoundary::NotWordBoundary,
+    ///     CharacterBoundary::WordBoundary,
+    ///     CharacterBoundary::WordBoundary,
+    ///     CharacterBoundary::NotWordBoundary,
+    ///     CharacterBoundary::WordBoundary,
+    ///     CharacterBoundary::NotWordBoundary,
+    ///     CharacterBoundary::Unknown,
+    /// ], s.boundaries());
+    /// ```
+    #[inline]
+    pub fn boundaries(&self) -> &[CharacterBoundary] {
+        &self.boundaries
+    }
+
+    /// Returns a mutable slice of boundary types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{CharacterBoundary, Sentence};
+    ///
+    /// let mut s = Sentence::from_partial_annotation("火-星|に|行-き|ま-し た").unwrap();
+    /// s.boundaries_mut()[6] = CharacterBoundary::WordBoundary;
+    /// let mut buf = String::new();
+    /// s.write_partial_annotation_text(&mut buf);
+    /// assert_eq!("火-星|に|行-き|ま-し|た", buf);
+    /// ```
+    #[inline]
+    pub fn boundaries_mut(&mut self) -> &mut [CharacterBoundary] {
+        &mut self.boundaries
+    }
+
+    /// Forces the given byte range of the text to be a single token.
+    ///
+    /// Boundaries inside the range become [`CharacterBoundary::NotWordBoundary`], and both ends
+    /// of the range become [`CharacterBoundary::WordBoundary`]. The constraint is applied to the
+    /// current boundaries immediately and is kept by [`Predictor::predict()`] and other functions
+    /// that respect constraints. It is cleared when the sentence is updated.
+    ///
+    /// # Errors
+    ///
+    /// An error variant will be returned when the range is empty, out of the text, not on
+    /// character boundaries, or conflicts with another constraint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut s = Sentence::from_tokenized("火星 猫 は 可愛 い").unwrap();
+    /// s.force_token(0..9).unwrap();
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星猫 は 可愛 い", buf);
+    ///
+    /// assert!(s.force_token(6..12).is_err());
+    /// assert!(s.force_token(1..3).is_err());
+    /// ```
+    pub fn force_token(&mut self, range: Range<usize>) -> Result<()> {
+        if range.start >= range.end {
+            return Err(VaporettoError::invalid_argument(
+                "range",
+                "must not be empty",
+            ));
+        }
+        let start = self.char_pos(range.start, "range")?;
+        let end = self.char_pos(range.end, "range")?;
+        if (start.saturating_sub(1)..end.min(self.boundaries.len()))
+            .any(|i| self.constraint_conflicts(i, Self::forced_boundary(i, start, end)))
+        {
+            return Err(VaporettoError::invalid_argument(
+                "range",
+                "conflicts with another constraint",
+            ));
+        }
+        self.init_constraints();
+        for i in start.saturating_sub(1)..end.min(self.boundaries.len()) {
+            let b = Self::forced_boundary(i, start, end);
+            self.constraints[i] = b;
+            self.boundaries[i] = b;
+        }
+        Ok(())
+    }
+
+    /// Forces a word boundary at the given byte position of the text.
+    ///
+    /// The constraint is applied to the current boundaries immediately and is kept by
+    /// [`Predictor::predict()`] and other functions that respect constraints. It is cleared when
+    /// the sentence is updated.
+    ///
+    /// # Errors
+    ///
+    /// An error variant will be returned when the position is not a boundary between two
+    /// characters or conflicts with another constraint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::Sentence;
+    ///
+    /// let mut s = Sentence::from_tokenized("火星猫 は 可愛い").unwrap();
+    /// s.force_boundary(6).unwrap();
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星 猫 は 可愛い", buf);
+    ///
+    /// assert!(s.force_boundary(0).is_err());
     /// ```
-    #[inline]
-    pub fn reset_tags(&mut self, n_tags: usize) {
-        self.tags.clear();
-        self.tags.resize(n_tags * self.len(), None);
-        self.n_tags = n_tags;
+    pub fn force_boundary(&mut self, pos: usize) -> Result<()> {
+        let i = self.char_pos(pos, "pos")?;
+        if i == 0 || i == self.len() {
+            return Err(VaporettoError::invalid_argument(
+                "pos",
+                "must be a boundary between two characters",
+            ));
+        }
+        if self.constraint_conflicts(i - 1, CharacterBoundary::WordBoundary) {
+            return Err(VaporettoError::invalid_argument(
+                "pos",
+                "conflicts with another constraint",
+            ));
+        }
+        self.init_constraints();
+        self.constraints[i - 1] = CharacterBoundary::WordBoundary;
+        self.boundaries[i - 1] = CharacterBoundary::WordBoundary;
+        Ok(())
     }
 
-    /// Returns a slice of character types.
+    /// Returns a slice of forced boundary types.
+    ///
+    /// Unconstrained positions are represented as [`CharacterBoundary::Unknown`]. If no
+    /// constraint is specified, an empty slice is returned.
     ///
     /// # Examples
     ///
     /// ```
-    /// use vaporetto::{CharacterType, Sentence};
+    /// use vaporetto::{CharacterBoundary, Sentence};
     ///
-    /// let s = Sentence::from_tokenized("火星 に 行き まし た").unwrap();
+    /// let mut s = Sentence::from_raw("火星猫だ").unwrap();
+    /// assert!(s.constraints().is_empty());
+    ///
+    /// s.force_token(0..6).unwrap();
     /// assert_eq!(&[
-    ///     CharacterType::Kanji as u8,
-    ///     CharacterType::Kanji as u8,
-    ///     CharacterType::Hiragana as u8,
-    ///     CharacterType::Kanji as u8,
-    ///     CharacterType::Hiragana as u8,
-    ///     CharacterType::Hiragana as u8,
-    ///     CharacterType::Hiragana as u8,
-    ///     CharacterType::Hiragana as u8,
-    /// ], s.char_types());
+    ///     CharacterBoundary::NotWordBoundary,
+    ///     CharacterBoundary::WordBoundary,
+    ///     CharacterBoundary::Unknown,
+    /// ], s.constraints());
     /// ```
     #[inline]
-    pub fn char_types(&self) -> &[u8] {
-        &self.char_types
+    pub fn constraints(&self) -> &[CharacterBoundary] {
+        &self.constraints
     }
 
-    /// Returns a slice of boundary types.
+    /// Removes all constraints. Current boundaries are not changed.
+    #[inline]
+    pub fn clear_constraints(&mut self) {
+        self.constraints.clear();
+    }
+
+    /// Overwrites boundaries with constraints specified by [`Sentence::force_token()`] and
+    /// [`Sentence::force_boundary()`].
+    ///
+    /// Functions that modify boundaries after prediction, such as rule-based filters, can call
+    /// this function to respect the constraints.
     ///
     /// # Examples
     ///
     /// ```
     /// use vaporetto::{CharacterBoundary, Sentence};
     ///
-    /// let s = Sentence::from_partial_annotation("火-星|に|行-き|ま-し た").unwrap();
-    /// assert_eq!(&[
-    ///     CharacterBoundary::NotWordBoundary,
-    ///     CharacterBoundary::WordBoundary,
-    ///     CharacterBoundary::WordBoundary,
-    ///     CharacterBoundary::NotWordBoundary,
-    ///     CharacterBoundary::WordBoundary,
-    ///     CharacterBoundary::NotWordBoundary,
-    ///     CharacterBoundary::Unknown,
-    /// ], s.boundaries());
+    /// let mut s = Sentence::from_raw("火星猫だ").unwrap();
+    /// s.force_token(0..6).unwrap();
+    /// s.boundaries_mut().fill(CharacterBoundary::WordBoundary);
+    /// s.apply_constraints();
+    /// let mut buf = String::new();
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("火星 猫 だ", buf);
     /// ```
-    #[inline]
-    pub fn boundaries(&self) -> &[CharacterBoundary] {
-        &self.boundaries
+    pub fn apply_constraints(&mut self) {
+        for (b, &c) in self.boundaries.iter_mut().zip(&self.constraints) {
+            if c != CharacterBoundary::Unknown {
+                *b = c;
+            }
+        }
     }
 
-    /// Returns a mutable slice of boundary types.
+    /// Enables the whitespace-preserving mode.
+    ///
+    /// Each run of whitespace characters is forced to be a single token, and such tokens are
+    /// excluded from [`Sentence::iter_tokens()`] and outputs based on it. Since the whitespace
+    /// remains in the text, offsets of tokens refer to the original text, which can be restored
+    /// exactly with [`Sentence::as_raw_text()`]. The constraints are kept by
+    /// [`Predictor::predict()`] and other functions that respect constraints. The mode is disabled
+    /// when the sentence is updated.
+    ///
+    /// # Errors
+    ///
+    /// An error variant will be returned when the whitespace conflicts with another constraint.
+    /// In this case, the sentence is not changed.
     ///
     /// # Examples
     ///
     /// ```
     /// use vaporetto::{CharacterBoundary, Sentence};
     ///
-    /// let mut s = Sentence::from_partial_annotation("火-星|に|行-き|ま-し た").unwrap();
-    /// s.boundaries_mut()[6] = CharacterBoundary::WordBoundary;
+    /// let mut s = Sentence::from_raw("Hello,  火星猫").unwrap();
+    /// s.boundaries_mut().fill(CharacterBoundary::WordBoundary);
+    /// s.preserve_whitespace().unwrap();
+    ///
     /// let mut buf = String::new();
-    /// s.write_partial_annotation_text(&mut buf);
-    /// assert_eq!("火-星|に|行-き|ま-し|た", buf);
+    /// s.write_tokenized_text(&mut buf);
+    /// assert_eq!("H e l l o , 火 星 猫", buf);
+    ///
+    /// let token = s.iter_tokens().nth(6).unwrap();
+    /// assert_eq!("火", token.surface());
+    /// assert_eq!(8, token.start());
+    /// assert_eq!("Hello,  火星猫", s.as_raw_text());
     /// ```
+    pub fn preserve_whitespace(&mut self) -> Result<()> {
+        let mut runs = vec![];
+        let mut run_start = None;
+        for (i, c) in self.text.chars().enumerate() {
+            if c.is_whitespace() {
+                run_start.get_or_insert(i);
+            } else if let Some(start) = run_start.take() {
+                runs.push(start..i);
+            }
+        }
+        if let Some(start) = run_start {
+            runs.push(start..self.len());
+        }
+        for run in &runs {
+            if (run.start.saturating_sub(1)..run.end.min(self.boundaries.len()))
+                .any(|i| self.constraint_conflicts(i, Self::forced_boundary(i, run.start, run.end)))
+            {
+                return Err(VaporettoError::invalid_argument(
+                    "text",
+                    "whitespace conflicts with another constraint",
+                ));
+            }
+        }
+        if !runs.is_empty() {
+            self.init_constraints();
+        }
+        for run in runs {
+            for i in run.start.saturating_sub(1)..run.end.min(self.boundaries.len()) {
+                let b = Self::forced_boundary(i, run.start, run.end);
+                self.constraints[i] = b;
+                self.boundaries[i] = b;
+            }
+        }
+        self.preserve_whitespace = true;
+        Ok(())
+    }
+
+    /// Returns `true` if the whitespace-preserving mode is enabled by
+    /// [`Sentence::preserve_whitespace()`].
     #[inline]
-    pub fn boundaries_mut(&mut self) -> &mut [CharacterBoundary] {
-        &mut self.boundaries
+    pub const fn preserves_whitespace(&self) -> bool {
+        self.preserve_whitespace
+    }
+
+    fn char_pos(&self, pos: usize, arg_name: &'static str) -> Result<usize> {
+        if !self.text.is_char_boundary(pos) {
+            return Err(VaporettoError::invalid_argument(
+                arg_name,
+                "must be on a character boundary of the text",
+            ));
+        }
+        Ok(self.str_to_char_pos[pos])
+    }
+
+    #[inline]
+    const fn forced_boundary(i: usize, start: usize, end: usize) -> CharacterBoundary {
+        if i + 1 == start || i + 1 == end {
+            CharacterBoundary::WordBoundary
+        } else {
+            CharacterBoundary::NotWordBoundary
+        }
+    }
+
+    #[inline]
+    fn constraint_conflicts(&self, i: usize, b: CharacterBoundary) -> bool {
+        matches!(
+            self.constraints.get(i),
+            Some(&c) if c != CharacterBoundary::Unknown && c != b
+        )
+    }
+
+    #[inline]
+    fn init_constraints(&mut self) {
+        if self.constraints.is_empty() {
+            self.constraints
+                .resize(self.boundaries.len(), CharacterBoundary::Unknown);
+        }
     }
 
     /// Returns a slice of boundary scores.
@@ -1066,6 +2179,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
     ///
     /// Tags can also be inserted at other positions, but such tags are ignored.
     ///
+    /// Since the tags may be modified, tag IDs returned by [`Token::tag_ids()`] are cleared.
+    ///
     /// # Examples
     ///
     /// ```
@@ -1086,6 +2201,8 @@ impl<'a, 'b> Sentence<'a, 'b> {
     /// ```
     #[inline]
     pub fn tags_mut(&mut self) -> &mut [Option<Cow<'b, str>>] {
+        #[cfg(feature = "tag-prediction")]
+        self.tag_ids.clear();
         &mut self.tags
     }
 
@@ -1151,6 +2268,68 @@ assert_eq!(
         self.n_tags
     }
 
+    /// Converts the sentence into one that owns its text and tags and does not borrow the
+    /// predictor.
+    ///
+    /// The returned sentence can be stored or sent to other threads independently of the
+    /// predictor. Since the reference to the predictor is dropped, call this function after
+    /// [`Sentence::fill_tags()`] if necessary. Internal buffers are reused, so the returned
+    /// sentence can also be given to [`Predictor::predict()`] again.
+    ///
+    #[cfg_attr(
+        all(feature = "std", feature = "tag-prediction"),
+        doc = "
+# Examples
+
+```
+use std::fs::File;
+
+use vaporetto::{Model, Predictor, Sentence};
+
+let f = File::open(\"../resources/model.bin\").unwrap();
+let model = Model::read(f).unwrap();
+let predictor = Predictor::new(model, true).unwrap();
+
+let mut s = Sentence::from_raw(\"まぁ良いだろう\").unwrap();
+predictor.predict(&mut s);
+s.fill_tags();
+let s: Sentence<'static, 'static> = s.into_owned();
+drop(predictor);
+
+let mut buf = String::new();
+s.write_tokenized_text(&mut buf);
+assert_eq!(
+    \"まぁ/副詞/マー 良い/形容詞/ヨイ だろう/助動詞/ダロー\",
+    buf,
+);
+```
+"
+    )]
+    pub fn into_owned(self) -> Sentence<'static, 'static> {
+        Sentence {
+            text: Cow::Owned(self.text.into_owned()),
+            char_types: self.char_types,
+            boundaries: self.boundaries,
+            constraints: self.constraints,
+            preserve_whitespace: self.preserve_whitespace,
+            boundary_scores: self.boundary_scores,
+            score_padding: self.score_padding,
+            char_pma_states: self.char_pma_states,
+            type_pma_states: self.type_pma_states,
+            tags: self
+                .tags
+                .into_iter()
+                .map(|tag| tag.map(|tag| Cow::Owned(tag.into_owned())))
+                .collect(),
+            n_tags: self.n_tags,
+            #[cfg(feature = "tag-prediction")]
+            tag_ids: self.tag_ids,
+            predictor: None,
+            str_to_char_pos: self.str_to_char_pos,
+            char_to_str_pos: self.char_to_str_pos,
+        }
+    }
+
     #[inline]
     pub(crate) fn len(&self) -> usize {
         self.char_types.len()
@@ -1203,6 +2382,69 @@ impl<'a, 'b> Token<'a, 'b> {
         &self.sentence.tags[start..end]
     }
 
+    /// Returns candidates of each tag of this token sorted in descending order of scores.
+    ///
+    /// The first candidate of each tag is the one selected by [`Sentence::fill_tags()`].
+    /// Candidates are calculated on demand, so this function can be called without
+    /// [`Sentence::fill_tags()`]. If the model cannot predict tags of this token, or the sentence
+    /// has not been given to [`Predictor::predict()`], empty lists are returned.
+    ///
+    /// # Panics
+    ///
+    /// The predictor must be created with `predict_tags = true`.
+    ///
+    #[cfg_attr(
+        feature = "std",
+        doc = "
+# Examples
+
+```
+use std::fs::File;
+
+use vaporetto::{Model, Predictor, Sentence};
+
+let f = File::open(\"../resources/model.bin\").unwrap();
+let model = Model::read(f).unwrap();
+let predictor = Predictor::new(model, true).unwrap();
+
+let mut s = Sentence::from_raw(\"まぁ良いだろう\").unwrap();
+predictor.predict(&mut s);
+s.fill_tags();
+
+for token in s.iter_tokens() {
+    let candidates = token.tag_candidates();
+    assert_eq!(token.tags().len(), candidates.len());
+    for (tag, cands) in token.tags().iter().zip(&candidates) {
+        assert_eq!(tag.as_deref(), cands.first().map(|c| c.tag()));
+        assert!(cands.windows(2).all(|w| w[0].score() >= w[1].score()));
+    }
+}
+```
+"
+    )]
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    pub fn tag_candidates(&self) -> Vec<Vec<TagCandidate<'b>>> {
+        self.sentence.predictor.map_or_else(Vec::new, |predictor| {
+            predictor.tag_candidates(self.sentence, self.start, self.end)
+        })
+    }
+
+    /// Returns IDs of tags of this token in the tag vocabulary of the predictor.
+    ///
+    /// The IDs are recorded when tags are filled by [`Sentence::fill_tags()`]. `None` is returned
+    /// for a tag that is not predicted, including a tag overwritten by a user dictionary. If the
+    /// tags have not been filled, or have been modified by [`Sentence::tags_mut()`], an empty
+    /// slice is returned. Use [`Predictor::tag_str()`] to get the tag string of an ID.
+    #[cfg(feature = "tag-prediction")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tag-prediction")))]
+    #[inline]
+    pub fn tag_ids(&self) -> &'a [Option<u32>] {
+        let start = (self.end - 1) * self.sentence.n_tags();
+        let end = self.end * self.sentence.n_tags();
+        self.sentence.tag_ids.get(start..end).unwrap_or_default()
+    }
+
     /// Returns the start position of this token in characters.
     #[inline]
     pub const fn start(&self) -> usize {
@@ -1225,6 +2467,19 @@ impl<'a, 'b> Iterator for TokenIterator<'a, 'b> {
     type Item = Token<'a, 'b>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = self.next_token()?;
+            if !self.token.sentence.preserve_whitespace
+                || !token.surface().chars().all(char::is_whitespace)
+            {
+                return Some(token);
+            }
+        }
+    }
+}
+
+impl<'a, 'b> TokenIterator<'a, 'b> {
+    fn next_token(&mut self) -> Option<Token<'a, 'b>> {
         self.token.start = self.token.end;
         if let Some(boundaries) = self.token.sentence.boundaries().get(self.token.start..) {
             let mut skip_token = false;
@@ -2614,44 +3869,249 @@ mod tests {
     }
 
     #[test]
-    fn test_sentence_to_tokenized_string_unknown() {
-        let s = Sentence::from_partial_annotation("火-星 猫|の|生-態").unwrap();
+    fn test_sentence_to_tokenized_string_unknown() {
+        let s = Sentence::from_partial_annotation("火-星 猫|の|生-態").unwrap();
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+
+        assert_eq!("の 生態", buf);
+    }
+
+    #[test]
+    fn test_sentence_to_tokenized_string() {
+        let s = Sentence::from_tokenized("Rust で 良い プログラミング 体験 を ！").unwrap();
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+
+        assert_eq!("Rust で 良い プログラミング 体験 を ！", buf);
+    }
+
+    #[test]
+    fn test_sentence_to_tokenized_string_with_tags() {
+        let s =
+            Sentence::from_tokenized("Rust/名詞 で 良い/形容詞 プログラミング 体験 を ！/補助記号")
+                .unwrap();
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+
+        assert_eq!(
+            "Rust/名詞 で 良い/形容詞 プログラミング 体験 を ！/補助記号",
+            buf,
+        );
+    }
+
+    #[test]
+    fn test_sentence_to_tokenized_string_escape() {
+        let s = Sentence::from_partial_annotation("火-星-猫|の| |生-態|\\-n").unwrap();
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+
+        assert_eq!("火星猫 の \\  生態 \\\\n", buf);
+    }
+
+    #[test]
+    fn test_sentence_from_tokenized_with_format() {
+        let format = TokenizedFormat::new('\t', '_', '%').unwrap();
+        let s = Sentence::from_tokenized_with_format("火 星_名詞\t猫%_%%_記号", &format).unwrap();
+
+        assert_eq!("火 星猫_%", s.as_raw_text());
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+            ],
+            s.boundaries(),
+        );
+        assert_eq!(
+            &[
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                None,
+                None,
+                Some(Cow::Borrowed("記号")),
+            ],
+            s.tags(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_update_tokenized_with_format() {
+        let format = TokenizedFormat::new('|', '_', '\\').unwrap();
+        let mut s = Sentence::from_raw("12345").unwrap();
+        s.update_tokenized_with_format("火 星_名詞|猫", &format)
+            .unwrap();
+
+        assert_eq!("火 星猫", s.as_raw_text());
+        assert_eq!(
+            &[NotWordBoundary, NotWordBoundary, WordBoundary],
+            s.boundaries(),
+        );
+        assert_eq!(&[None, None, Some(Cow::Borrowed("名詞")), None], s.tags(),);
+
+        assert!(s.update_tokenized_with_format("火星||猫", &format).is_err());
+        assert_eq!(" ", s.as_raw_text());
+    }
+
+    #[test]
+    fn test_sentence_to_tokenized_string_with_format() {
+        let format = TokenizedFormat::new('\t', '_', '%').unwrap();
+        let s = Sentence::from_tokenized("火星/名詞 猫_% の/助詞/ノ").unwrap();
         let mut buf = String::new();
-        s.write_tokenized_text(&mut buf);
+        s.write_tokenized_text_with_format(&mut buf, &format);
 
-        assert_eq!("の 生態", buf);
+        assert_eq!("火星_名詞\t猫%_%%\tの_助詞_ノ", buf);
+
+        let s2 = Sentence::from_tokenized_with_format(&buf, &format).unwrap();
+        assert_eq!(s.as_raw_text(), s2.as_raw_text());
+        assert_eq!(s.boundaries(), s2.boundaries());
+        assert_eq!(s.tags(), s2.tags());
     }
 
     #[test]
-    fn test_sentence_to_tokenized_string() {
-        let s = Sentence::from_tokenized("Rust で 良い プログラミング 体験 を ！").unwrap();
+    fn test_sentence_from_tokenized_empty_tag() {
+        let s = Sentence::from_tokenized("火星/\\0/名詞 猫//\\0x/\\00 の/\\\\0").unwrap();
+
+        assert_eq!(3, s.n_tags());
+        assert_eq!(
+            &[
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("")),
+                Some(Cow::Borrowed("名詞")),
+                None,
+                None,
+                Some(Cow::Borrowed("0x")),
+                Some(Cow::Borrowed("00")),
+                Some(Cow::Borrowed("\\0")),
+                None,
+                None,
+            ],
+            s.tags(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_to_tokenized_string_empty_tag() {
+        let mut s = Sentence::from_tokenized("火星 猫 の").unwrap();
+        s.reset_tags(3);
+        s.tags_mut()[3] = Some(Cow::Borrowed(""));
+        s.tags_mut()[4] = Some(Cow::Borrowed("0"));
+        s.tags_mut()[6] = Some(Cow::Borrowed("\\0"));
         let mut buf = String::new();
         s.write_tokenized_text(&mut buf);
 
-        assert_eq!("Rust で 良い プログラミング 体験 を ！", buf);
+        assert_eq!("火星/\\0/0 猫/\\\\0 の///", buf);
     }
 
     #[test]
-    fn test_sentence_to_tokenized_string_with_tags() {
-        let s =
-            Sentence::from_tokenized("Rust/名詞 で 良い/形容詞 プログラミング 体験 を ！/補助記号")
-                .unwrap();
+    fn test_sentence_to_tokenized_string_without_tags() {
+        let mut s = Sentence::from_tokenized("火星 猫 の").unwrap();
+        s.reset_tags(2);
         let mut buf = String::new();
         s.write_tokenized_text(&mut buf);
 
-        assert_eq!(
-            "Rust/名詞 で 良い/形容詞 プログラミング 体験 を ！/補助記号",
-            buf,
-        );
+        assert_eq!("火星 猫 の", buf);
+        assert_eq!(0, Sentence::from_tokenized(&buf).unwrap().n_tags());
+    }
+
+    /// Generates a fully annotated sentence from a pseudo-random number generator.
+    fn random_sentence(seed: &mut u64) -> Sentence<'static, 'static> {
+        const CHARS: &[char] = &['a', '0', 'あ', '漢', ' ', '/', '\\', '|', '_', '\t'];
+        const TAGS: &[Option<&str>] = &[
+            None,
+            Some(""),
+            Some("0"),
+            Some("\\0"),
+            Some("名詞"),
+            Some("a/b"),
+            Some("x y"),
+            Some("\\"),
+            Some("0\\"),
+        ];
+        let mut next = |n: usize| {
+            *seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            usize::try_from(*seed >> 33).unwrap() % n
+        };
+        let len = next(8) + 1;
+        let text: String = (0..len).map(|_| CHARS[next(CHARS.len())]).collect();
+        let n_tags = next(4);
+        let mut s = Sentence::from_raw(text).unwrap();
+        for b in s.boundaries_mut() {
+            *b = if next(2) == 0 {
+                WordBoundary
+            } else {
+                NotWordBoundary
+            };
+        }
+        s.reset_tags(n_tags);
+        for i in 0..len {
+            if i + 1 != len && s.boundaries()[i] == NotWordBoundary {
+                continue;
+            }
+            for j in 0..n_tags {
+                s.tags_mut()[i * n_tags + j] = TAGS[next(TAGS.len())].map(Cow::Borrowed);
+            }
+        }
+        s
     }
 
     #[test]
-    fn test_sentence_to_tokenized_string_escape() {
-        let s = Sentence::from_partial_annotation("火-星-猫|の| |生-態|\\-n").unwrap();
+    fn test_sentence_tokenized_round_trip() {
+        // Separators and escape characters also appear in surfaces and tags.
+        let formats = [
+            TokenizedFormat::default(),
+            TokenizedFormat::new('\t', '_', '|').unwrap(),
+            TokenizedFormat::new('|', 'a', '/').unwrap(),
+            TokenizedFormat::new('a', ' ', '\t').unwrap(),
+        ];
+        let mut seed = 0;
         let mut buf = String::new();
-        s.write_tokenized_text(&mut buf);
+        let mut n_untagged = 0;
+        for _ in 0..1000 {
+            let s = random_sentence(&mut seed);
+            for format in &formats {
+                s.write_tokenized_text_with_format(&mut buf, format);
+                let s2 = Sentence::from_tokenized_with_format(&buf, format).unwrap();
+
+                assert_eq!(s.as_raw_text(), s2.as_raw_text(), "{buf}");
+                assert_eq!(s.boundaries(), s2.boundaries(), "{buf}");
+                if s.tags().iter().any(Option::is_some) {
+                    assert_eq!(s.n_tags(), s2.n_tags(), "{buf}");
+                    assert_eq!(s.tags(), s2.tags(), "{buf}");
+                } else {
+                    // Sentences without any tags are written without tag fields, so the number of
+                    // tags is restored as 0 even if the original sentence has missing tags.
+                    assert_eq!(0, s2.n_tags(), "{buf}");
+                    assert!(s2.tags().is_empty(), "{buf}");
+                    if s.n_tags() != 0 {
+                        n_untagged += 1;
+                    }
+                }
+            }
+        }
+        assert_ne!(0, n_untagged);
+    }
 
-        assert_eq!("火星猫 の \\  生態 \\\\n", buf);
+    #[test]
+    fn test_tokenized_format_invalid() {
+        assert!(TokenizedFormat::new('\0', '/', '\\').is_err());
+        assert!(TokenizedFormat::new('0', '/', '\\').is_err());
+        assert!(TokenizedFormat::new(' ', '0', '\\').is_err());
+        assert!(TokenizedFormat::new(' ', '/', '0').is_err());
+        assert!(TokenizedFormat::new(' ', ' ', '\\').is_err());
+        assert!(TokenizedFormat::new(' ', '/', '/').is_err());
+        assert!(TokenizedFormat::new('\\', '/', '\\').is_err());
+        assert_eq!(
+            TokenizedFormat::default(),
+            TokenizedFormat::new(' ', '/', '\\').unwrap(),
+        );
     }
 
     #[test]
@@ -2894,4 +4354,434 @@ mod tests {
         );
         assert!(s.boundary_scores().is_empty());
     }
+
+    #[test]
+    fn test_sentence_force_token() {
+        let mut s = Sentence::from_raw("Rustで良い").unwrap();
+        s.force_token(0..4).unwrap();
+        s.force_token(4..7).unwrap();
+        assert_eq!(
+            [
+                NotWordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                Unknown,
+            ],
+            s.constraints()
+        );
+        assert_eq!(s.constraints(), s.boundaries());
+    }
+
+    #[test]
+    fn test_sentence_force_token_whole() {
+        let mut s = Sentence::from_raw("良い").unwrap();
+        s.force_token(0..6).unwrap();
+        assert_eq!([NotWordBoundary], s.constraints());
+    }
+
+    #[test]
+    fn test_sentence_force_token_invalid() {
+        let mut s = Sentence::from_raw("Rustで良い").unwrap();
+        assert!(s.force_token(2..2).is_err());
+        assert!(s.force_token(0..5).is_err());
+        assert!(s.force_token(0..14).is_err());
+        assert!(s.constraints().is_empty());
+        s.force_token(1..4).unwrap();
+        assert!(s.force_token(2..7).is_err());
+        assert!(s.force_boundary(2).is_err());
+        assert!(s.force_boundary(10).is_ok());
+        assert!(s.force_boundary(13).is_err());
+    }
+
+    #[test]
+    fn test_sentence_update_clears_constraints() {
+        let mut s = Sentence::from_raw("Rustで良い").unwrap();
+        s.force_boundary(4).unwrap();
+        s.update_raw("Rustで良い").unwrap();
+        assert!(s.constraints().is_empty());
+        assert_eq!([Unknown; 6], s.boundaries());
+    }
+
+    #[test]
+    fn test_sentence_preserve_whitespace() {
+        let mut s = Sentence::from_tokenized_with_format(
+            "\t Rust|で|良い|　|\n",
+            &TokenizedFormat::new('|', '/', '\\').unwrap(),
+        )
+        .unwrap();
+        s.boundaries_mut()[1] = WordBoundary;
+        s.preserve_whitespace().unwrap();
+
+        assert!(s.preserves_whitespace());
+        assert_eq!(
+            [
+                NotWordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+            ],
+            s.boundaries(),
+        );
+        let tokens: Vec<_> = s
+            .iter_tokens()
+            .map(|token| (token.surface(), token.start(), token.end()))
+            .collect();
+        assert_eq!(vec![("Rust", 2, 6), ("で", 6, 7), ("良い", 7, 9)], tokens);
+        assert_eq!("\t Rustで良い　\n", s.as_raw_text());
+
+        s.update_raw("Rust で").unwrap();
+        assert!(!s.preserves_whitespace());
+        assert!(s.constraints().is_empty());
+    }
+
+    #[test]
+    fn test_sentence_detokenize() {
+        let mut buf = String::new();
+
+        let s = Sentence::from_tokenized("火星 猫 の 生態 （ 2 ） を 調査 。").unwrap();
+        s.detokenize(&mut buf);
+        assert_eq!("火星猫の生態（2）を調査。", buf);
+
+        let s = Sentence::from_tokenized("He said , “ it 's ( not ) 100 % ! ”").unwrap();
+        s.detokenize(&mut buf);
+        assert_eq!("He said, “it's (not) 100%!”", buf);
+
+        let s = Sentence::from_partial_annotation("A-B|C|D E|F").unwrap();
+        s.detokenize(&mut buf);
+        assert_eq!("AB C F", buf);
+    }
+
+    #[test]
+    fn test_sentence_detokenize_preserve_whitespace() {
+        let mut buf = String::new();
+
+        let mut s = Sentence::from_raw(" Rust  で\t良い ").unwrap();
+        s.boundaries_mut().fill(WordBoundary);
+        s.preserve_whitespace().unwrap();
+        s.detokenize(&mut buf);
+        assert_eq!(" Rust  で\t良い ", buf);
+
+        s.boundaries_mut()[1] = Unknown;
+        s.detokenize(&mut buf);
+        assert_eq!("st  で\t良い ", buf);
+
+        let mut s = Sentence::from_raw("   ").unwrap();
+        s.preserve_whitespace().unwrap();
+        s.detokenize(&mut buf);
+        assert_eq!("   ", buf);
+    }
+
+    #[test]
+    fn test_sentence_preserve_whitespace_conflict() {
+        let mut s = Sentence::from_raw("Rust で").unwrap();
+        s.force_token(3..5).unwrap();
+        assert!(s.preserve_whitespace().is_err());
+        assert!(!s.preserves_whitespace());
+        assert_eq!(
+            [
+                Unknown,
+                Unknown,
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary
+            ],
+            s.constraints()
+        );
+    }
+
+    #[test]
+    fn test_sentence_into_owned() {
+        fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+        let text = String::from("火星猫だ");
+        let mut s = Sentence::from_tokenized("火星/名詞 猫 だ/助動詞/ダ").unwrap();
+        s.update_raw(text.as_str()).unwrap();
+        s.boundaries_mut()[1] = WordBoundary;
+        s.reset_tags(2);
+        s.tags_mut()[3] = Some(Cow::Borrowed("名詞"));
+        s.force_boundary(6).unwrap();
+        let s = s.into_owned();
+        drop(text);
+
+        assert_send_static(&s);
+        assert_eq!("火星猫だ", s.as_raw_text());
+        assert_eq!(&[Unknown, WordBoundary, Unknown], s.boundaries());
+        assert_eq!(&[Unknown, WordBoundary, Unknown], s.constraints());
+        assert_eq!(Some("名詞"), s.tags()[3].as_deref());
+    }
+
+    #[test]
+    fn test_sentence_from_conllu() {
+        let s = Sentence::from_conllu(
+            "# sent_id = 1\n\
+             # text = 東京に行った\n\
+             1\t東京\t東京\tPROPN\t名詞-固有名詞\t_\t3\tobl\t_\tSpaceAfter=No\n\
+             2\tに\tに\tADP\t助詞-格助詞\t_\t1\tcase\t_\tSpaceAfter=No\n\
+             3-4\t行った\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+             3\t行っ\t行く\tVERB\t_\t_\t0\troot\t_\tSpaceAfter=No\n\
+             3.1\t行っ\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             4\tた\tた\tAUX\t助動詞\t_\t3\taux\t_\tSpaceAfter=No\n\
+             \n",
+        )
+        .unwrap();
+
+        assert_eq!("東京に行った", s.as_raw_text());
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary
+            ],
+            s.boundaries(),
+        );
+        assert_eq!(2, s.n_tags());
+        assert_eq!(
+            &[
+                None,
+                None,
+                Some(Cow::Borrowed("PROPN")),
+                Some(Cow::Borrowed("名詞-固有名詞")),
+                Some(Cow::Borrowed("ADP")),
+                Some(Cow::Borrowed("助詞-格助詞")),
+                None,
+                None,
+                Some(Cow::Borrowed("VERB")),
+                None,
+                Some(Cow::Borrowed("AUX")),
+                Some(Cow::Borrowed("助動詞")),
+            ],
+            s.tags(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_from_conllu_space_after() {
+        let s = Sentence::from_conllu(
+            "# text = Mars cats aren't here\n\
+             1\tMars\tMars\tPROPN\t_\t_\t_\t_\t_\t_\n\
+             2\tcats\tcat\tNOUN\t_\t_\t_\t_\t_\tSpacesAfter=\\s\n\
+             3-4\taren't\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             3\tare\tbe\tAUX\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+             4\tn't\tnot\tPART\t_\t_\t_\t_\t_\t_\n\
+             5\there\there\tADV\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+             \n",
+        )
+        .unwrap();
+
+        assert_eq!("Mars cats aren't here", s.as_raw_text());
+        assert!(s.preserves_whitespace());
+        assert_eq!(
+            vec!["Mars", "cats", "are", "n't", "here"],
+            s.iter_tokens().map(|t| t.surface()).collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            vec![
+                Some("PROPN"),
+                Some("NOUN"),
+                Some("AUX"),
+                Some("PART"),
+                Some("ADV")
+            ],
+            s.iter_tokens()
+                .map(|t| t.tags()[0].as_deref())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_from_conllu_invalid() {
+        assert_eq!(
+            "InvalidArgumentError: conllu_text: must contain at least one token",
+            &Sentence::from_conllu("# text = \n\n")
+                .err()
+                .unwrap()
+                .to_string(),
+        );
+        assert_eq!(
+            "InvalidArgumentError: conllu_text: each token line must contain 10 fields",
+            &Sentence::from_conllu("1\t東京\t東京\tPROPN\n")
+                .err()
+                .unwrap()
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_update_conllu_invalid() {
+        let mut s = Sentence::from_raw("12345").unwrap();
+        assert!(s.update_conllu("1\t\t_\t_\t_\t_\t_\t_\t_\t_\n").is_err());
+
+        assert_eq!(" ", s.as_raw_text());
+        assert_eq!([0, 1], s.char_to_str_pos());
+        assert!(s.boundaries().is_empty());
+    }
+
+    #[test]
+    fn test_sentence_write_conllu() {
+        let s = Sentence::from_partial_annotation("火-星/名詞|猫 の|生-態/名詞").unwrap();
+        let mut buf = String::new();
+        s.write_conllu(&mut buf);
+
+        assert_eq!(
+            "# text = 火星猫の生態\n\
+             1\t火星\t_\t名詞\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+             2\t生態\t_\t名詞\t_\t_\t_\t_\t_\t_\n\
+             \n",
+            buf,
+        );
+    }
+
+    #[test]
+    fn test_sentence_write_conllu_preserve_whitespace() {
+        let mut s = Sentence::from_raw("火星 の猫\u{3000}だ").unwrap();
+        s.boundaries_mut().fill(WordBoundary);
+        s.boundaries_mut()[0] = NotWordBoundary;
+        s.preserve_whitespace().unwrap();
+        let mut buf = String::new();
+        s.write_conllu(&mut buf);
+
+        assert_eq!(
+            "# text = 火星 の猫\u{3000}だ\n\
+             1\t火星\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             2\tの\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+             3\t猫\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             4\tだ\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             \n",
+            buf,
+        );
+    }
+
+    #[test]
+    fn test_sentence_conllu_round_trip() {
+        let s = Sentence::from_tokenized("火星/NOUN/名詞 猫/NOUN の/ADP 生態/NOUN/名詞").unwrap();
+        let mut buf = String::new();
+        s.write_conllu(&mut buf);
+        let mut s2 = Sentence::default();
+        s2.update_conllu(&buf).unwrap();
+
+        assert_eq!(s.as_raw_text(), s2.as_raw_text());
+        assert_eq!(s.boundaries(), s2.boundaries());
+        assert_eq!(s.tags(), s2.tags());
+    }
+
+    #[test]
+    fn test_sentence_conllu_round_trip_preserve_whitespace() {
+        let mut s = Sentence::from_raw("Hello, 火星 の猫").unwrap();
+        s.boundaries_mut().fill(WordBoundary);
+        s.boundaries_mut()[0] = NotWordBoundary;
+        s.preserve_whitespace().unwrap();
+        s.reset_tags(1);
+        s.tags_mut()[8] = Some("名詞".into());
+        let mut buf = String::new();
+        s.write_conllu(&mut buf);
+        let s2 = Sentence::from_conllu(&buf).unwrap();
+
+        assert_eq!(s.as_raw_text(), s2.as_raw_text());
+        assert_eq!(s.boundaries(), s2.boundaries());
+        let mut buf2 = String::new();
+        s2.write_conllu(&mut buf2);
+        assert_eq!(buf, buf2);
+    }
+
+    #[test]
+    fn test_sentence_from_tsv() {
+        let s = Sentence::from_tsv("Rust\t名詞\nで\nは\t\tワ\n").unwrap();
+
+        assert_eq!("Rustでは", s.as_raw_text());
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                WordBoundary
+            ],
+            s.boundaries(),
+        );
+        assert_eq!(2, s.n_tags());
+        assert_eq!(
+            &[
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("名詞")),
+                None,
+                None,
+                None,
+                None,
+                Some(Cow::Borrowed("ワ")),
+            ],
+            s.tags(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_from_tsv_invalid() {
+        assert_eq!(
+            "InvalidArgumentError: tsv_text: must contain at least one token",
+            &Sentence::from_tsv("\n").err().unwrap().to_string(),
+        );
+        assert_eq!(
+            "InvalidArgumentError: tsv_text: must not contain empty tokens",
+            &Sentence::from_tsv("火星\n\t名詞\n")
+                .err()
+                .unwrap()
+                .to_string(),
+        );
+        assert_eq!(
+            "InvalidArgumentError: tsv_text: must not contain NULL",
+            &Sentence::from_tsv("火\0星\n").err().unwrap().to_string(),
+        );
+    }
+
+    #[test]
+    fn test_sentence_tsv_round_trip() {
+        let s = Sentence::from_tokenized("火星/名詞/カセー 猫//ネコ の 生態/名詞").unwrap();
+        let mut buf = String::new();
+        s.write_tsv(&mut buf);
+
+        assert_eq!("火星\t名詞\tカセー\n猫\t\tネコ\nの\n生態\t名詞\n\n", buf);
+
+        let mut s2 = Sentence::from_raw("12345").unwrap();
+        s2.update_tsv(&buf).unwrap();
+
+        assert_eq!(s.as_raw_text(), s2.as_raw_text());
+        assert_eq!(s.boundaries(), s2.boundaries());
+        assert_eq!(s.tags(), s2.tags());
+    }
+
+    #[test]
+    fn test_sentence_write_mecab_text() {
+        let s = Sentence::from_partial_annotation("火-星/名詞|猫 の|生-態/名詞,一般/セ\"イタイ")
+            .unwrap();
+        let mut buf = String::new();
+        s.write_mecab_text(&mut buf, &[Some(0), Some(2), None, Some(1)]);
+
+        assert_eq!(
+            "火星\t名詞,*,*,*\n生態\t\"名詞,一般\",*,*,\"セ\"\"イタイ\"\nEOS\n",
+            buf,
+        );
+    }
+
+    #[test]
+    fn test_sentence_write_mecab_text_without_tags() {
+        let s = Sentence::from_tokenized("火星 猫").unwrap();
+        let mut buf = String::new();
+        s.write_mecab_text(&mut buf, &[]);
+
+        assert_eq!("火星\t\n猫\t\nEOS\n", buf);
+    }
 }
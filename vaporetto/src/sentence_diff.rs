@@ -0,0 +1,200 @@
+use alloc::vec::Vec;
+
+use crate::errors::{Result, VaporettoError};
+use crate::sentence::{CharacterBoundary, Sentence};
+
+/// Kind of a difference between two segmentations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    /// A single token of the left sentence is split into multiple tokens in the right sentence.
+    OverSplit,
+
+    /// Multiple tokens of the left sentence are merged into a single token in the right
+    /// sentence.
+    UnderSplit,
+
+    /// Tokens of both sentences cross each other.
+    Crossing,
+
+    /// Both sentences have the same token, but their tags are different.
+    TagMismatch,
+}
+
+/// Difference between two segmentations of the same text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenDiff {
+    /// Kind of the difference.
+    pub kind: DiffKind,
+
+    /// Start position of the span in characters.
+    pub start: usize,
+
+    /// End position of the span in characters.
+    pub end: usize,
+}
+
+impl<'a, 'b> Sentence<'a, 'b> {
+    /// Aligns two segmentations of the same text and returns their differences.
+    ///
+    /// The text is divided into spans at positions where both sentences have word boundaries, and
+    /// a difference is reported for each span whose tokens or tags are different. Tags are
+    /// compared up to the larger number of tags, and missing tags are treated as `None`. Spans
+    /// containing [`CharacterBoundary::Unknown`] in either sentence are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - A sentence compared with this sentence, such as an output of a model when this
+    ///   sentence is a gold annotation.
+    ///
+    /// # Errors
+    ///
+    /// If the texts of the sentences are different, an error variant will be returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vaporetto::{DiffKind, Sentence, TokenDiff};
+    ///
+    /// let gold = Sentence::from_tokenized("火星猫 の 生態/名詞 を 調べ た").unwrap();
+    /// let sys = Sentence::from_tokenized("火星 猫 の 生態/動詞 を 調べた").unwrap();
+    /// assert_eq!(
+    ///     vec![
+    ///         TokenDiff { kind: DiffKind::OverSplit, start: 0, end: 3 },
+    ///         TokenDiff { kind: DiffKind::TagMismatch, start: 4, end: 6 },
+    ///         TokenDiff { kind: DiffKind::UnderSplit, start: 7, end: 10 },
+    ///     ],
+    ///     gold.diff(&sys).unwrap(),
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Sentence) -> Result<Vec<TokenDiff>> {
+        if self.as_raw_text() != other.as_raw_text() {
+            return Err(VaporettoError::invalid_argument(
+                "other",
+                "must have the same text",
+            ));
+        }
+        let mut diffs = vec![];
+        let mut start = 0;
+        let mut left_split = false;
+        let mut right_split = false;
+        let mut unknown = false;
+        for (i, (&l, &r)) in self
+            .boundaries()
+            .iter()
+            .zip(other.boundaries())
+            .chain([(
+                &CharacterBoundary::WordBoundary,
+                &CharacterBoundary::WordBoundary,
+            )])
+            .enumerate()
+        {
+            if l != CharacterBoundary::WordBoundary || r != CharacterBoundary::WordBoundary {
+                unknown |= l == CharacterBoundary::Unknown || r == CharacterBoundary::Unknown;
+                left_split |= l == CharacterBoundary::WordBoundary;
+                right_split |= r == CharacterBoundary::WordBoundary;
+                continue;
+            }
+            let end = i + 1;
+            let kind = match (left_split, right_split) {
+                _ if unknown => None,
+                (false, false) => (!self.same_tags(other, end)).then_some(DiffKind::TagMismatch),
+                (false, true) => Some(DiffKind::OverSplit),
+                (true, false) => Some(DiffKind::UnderSplit),
+                (true, true) => Some(DiffKind::Crossing),
+            };
+            if let Some(kind) = kind {
+                diffs.push(TokenDiff { kind, start, end });
+            }
+            start = end;
+            left_split = false;
+            right_split = false;
+            unknown = false;
+        }
+        Ok(diffs)
+    }
+
+    /// Returns `true` if the tags of tokens ending at the given position are the same.
+    fn same_tags(&self, other: &Sentence, end: usize) -> bool {
+        (0..self.n_tags().max(other.n_tags()))
+            .all(|j| token_tag(self, end, j) == token_tag(other, end, j))
+    }
+}
+
+/// Returns the `j`-th tag of the token ending at the given position.
+fn token_tag<'s>(sentence: &'s Sentence, end: usize, j: usize) -> Option<&'s str> {
+    if j < sentence.n_tags() {
+        sentence.tags()[(end - 1) * sentence.n_tags() + j].as_deref()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    fn diff(left: &str, right: &str) -> Vec<(DiffKind, usize, usize)> {
+        let left = Sentence::from_tokenized(left).unwrap();
+        let right = Sentence::from_tokenized(right).unwrap();
+        left.diff(&right)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.kind, d.start, d.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_same() {
+        assert!(diff("火星 猫/名詞 の", "火星 猫/名詞 の").is_empty());
+    }
+
+    #[test]
+    fn test_diff_splits() {
+        assert_eq!(
+            vec![
+                (DiffKind::OverSplit, 0, 3),
+                (DiffKind::UnderSplit, 4, 6),
+                (DiffKind::Crossing, 6, 10),
+            ],
+            diff("火星猫 の 生 態 を調 べた", "火星 猫 の 生態 を 調べ た"),
+        );
+    }
+
+    #[test]
+    fn test_diff_tags() {
+        assert_eq!(
+            vec![(DiffKind::TagMismatch, 0, 2), (DiffKind::TagMismatch, 3, 4)],
+            diff("火星/名詞 猫/名詞 は/助詞", "火星/名詞/カセー 猫/名詞 は"),
+        );
+        assert_eq!(
+            vec![(DiffKind::OverSplit, 0, 3)],
+            diff("火星猫/名詞 は", "火星/名詞 猫/名詞 は"),
+        );
+    }
+
+    #[test]
+    fn test_diff_unknown() {
+        let left = Sentence::from_partial_annotation("火-星 猫|の|生-態").unwrap();
+        let right = Sentence::from_tokenized("火 星 猫 の 生 態").unwrap();
+        assert_eq!(
+            vec![TokenDiff {
+                kind: DiffKind::OverSplit,
+                start: 4,
+                end: 6,
+            }],
+            left.diff(&right).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_diff_different_text() {
+        let left = Sentence::from_tokenized("火星 猫").unwrap();
+        let right = Sentence::from_tokenized("火星 犬").unwrap();
+        assert_eq!(
+            "InvalidArgumentError: other: must have the same text",
+            &left.diff(&right).err().unwrap().to_string(),
+        );
+    }
+}
@@ -1,15 +1,21 @@
 use alloc::borrow::Cow;
 use alloc::collections::BTreeMap;
 use alloc::string::ToString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
 use hashbrown::HashMap;
 use liblinear::LibLinearModel;
 
 use crate::errors::{Result, VaporettoError};
-use crate::model::TagModel;
+use crate::model::{TagContextWeight, TagModel, TagVocabularyBuilder};
 use crate::ngram_model::{TagNgramData, TagNgramModel, TagWeight};
+use crate::oov_tag_model::{for_each_oov_feature, OovTagModel, OovTagWeight};
 use crate::sentence::Sentence;
-use crate::trainer::{NgramFeature, SolverType};
+use crate::trainer::{
+    replicate_weighted_examples, NgramFeature, SolverType, TrainingObserver, TrainingProgress,
+};
 
 use crate::trainer::QUANTIZE_BIT_DEPTH;
 
@@ -17,6 +23,9 @@ use crate::trainer::QUANTIZE_BIT_DEPTH;
 enum TagFeature<'a> {
     CharacterNgram(NgramFeature<&'a str>),
     CharacterTypeNgram(NgramFeature<&'a [u8]>),
+    // Surfaces of the neighboring tokens. An empty string indicates the end of the sentence.
+    PreviousToken(&'a str),
+    NextToken(&'a str),
 }
 
 impl<'a> TagFeature<'a> {
@@ -39,6 +48,15 @@ impl<'a> TagFeature<'a> {
 struct TagExample<'a> {
     tags: &'a [Option<Cow<'a, str>>],
     features: Vec<TagFeature<'a>>,
+    weight: f64,
+}
+
+#[derive(Debug)]
+struct OovExample<'a> {
+    surface: &'a str,
+    char_types: &'a [u8],
+    tags: &'a [Option<Cow<'a, str>>],
+    weight: f64,
 }
 
 pub struct TagTrainer<'a> {
@@ -46,9 +64,12 @@ pub struct TagTrainer<'a> {
     char_ngram_size: u8,
     _type_window_size: u8,
     type_ngram_size: u8,
+    token_context: bool,
     default_tags: HashMap<&'a str, &'a [Option<Cow<'a, str>>]>,
     // Uses BTreeMap to improve compression ratio.
     examples: BTreeMap<&'a str, Vec<TagExample<'a>>>,
+    oov_slots: Vec<usize>,
+    oov_examples: Vec<OovExample<'a>>,
 }
 
 impl<'a> TagTrainer<'a> {
@@ -64,13 +85,25 @@ impl<'a> TagTrainer<'a> {
             char_ngram_size,
             _type_window_size: type_window_size,
             type_ngram_size,
+            token_context: false,
             default_tags,
             examples: BTreeMap::new(),
+            oov_slots: vec![],
+            oov_examples: vec![],
         }
     }
 
-    pub fn add_example<'b>(&mut self, sentence: &'a Sentence<'a, 'b>) {
-        for token in sentence.iter_tokens() {
+    pub fn set_oov_slots(&mut self, slots: Vec<usize>) {
+        self.oov_slots = slots;
+    }
+
+    pub fn set_token_context(&mut self, enabled: bool) {
+        self.token_context = enabled;
+    }
+
+    pub fn add_example<'b>(&mut self, sentence: &'a Sentence<'a, 'b>, weight: f64) {
+        let tokens: Vec<_> = sentence.iter_tokens().collect();
+        for (i, token) in tokens.iter().enumerate() {
             if token.tags().is_empty() {
                 continue;
             }
@@ -98,12 +131,38 @@ impl<'a> TagTrainer<'a> {
                     ));
                 }
             }
+            if self.token_context {
+                // Neighboring tokens are unknown if they are adjacent to unannotated boundaries.
+                if token.start() == 0 {
+                    features.push(TagFeature::PreviousToken(""));
+                } else if let Some(prev) = i.checked_sub(1).map(|j| &tokens[j]) {
+                    if prev.end() == token.start() {
+                        features.push(TagFeature::PreviousToken(prev.surface()));
+                    }
+                }
+                if token.end() == sentence.len() {
+                    features.push(TagFeature::NextToken(""));
+                } else if let Some(next) = tokens.get(i + 1) {
+                    if next.start() == token.end() {
+                        features.push(TagFeature::NextToken(next.surface()));
+                    }
+                }
+            }
+            if !self.oov_slots.is_empty() {
+                self.oov_examples.push(OovExample {
+                    surface: token.surface(),
+                    char_types: &sentence.char_types()[token.start()..token.end()],
+                    tags: token.tags(),
+                    weight,
+                });
+            }
             self.examples
                 .entry(token.surface())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(TagExample {
                     tags: token.tags(),
                     features,
+                    weight,
                 });
         }
     }
@@ -117,10 +176,12 @@ impl<'a> TagTrainer<'a> {
         HashMap<&'b TagFeature<'a>, u32>,
         Vec<Vec<(u32, f64)>>,
         Vec<f64>,
+        Vec<f64>,
     ) {
         let mut feature_ids = HashMap::new();
         let mut xs = vec![];
         let mut ys = vec![];
+        let mut weights = vec![];
         for example in examples {
             if let Some(tag) = example.tags.get(idx).and_then(|tag| tag.as_ref()) {
                 ys.push(tag_ids[tag.as_ref()] as f64)
@@ -134,13 +195,15 @@ impl<'a> TagTrainer<'a> {
                 feature_vec.push((feature_id, 1f64));
             }
             xs.push(feature_vec);
+            weights.push(example.weight);
         }
-        (feature_ids, xs, ys)
+        (feature_ids, xs, ys, weights)
     }
 
     fn train_tag(
         token: String,
         examples: &[TagExample<'a>],
+        vocabulary: &TagVocabularyBuilder,
         epsilon: f64,
         cost: f64,
         solver: SolverType,
@@ -154,7 +217,7 @@ impl<'a> TagTrainer<'a> {
                     if !tag_ids.contains_key(tag.as_ref()) {
                         let new_id = tag_ids.len();
                         tag_ids.insert(tag.as_ref(), new_id);
-                        tags.push(tag.to_string());
+                        tags.push(vocabulary.get(tag).unwrap());
                     }
                 }
             }
@@ -164,10 +227,13 @@ impl<'a> TagTrainer<'a> {
             .fold(0, |acc, x| acc + if x.len() >= 2 { x.len() } else { 0 });
 
         let mut bias = vec![0; n_class];
+        let mut quantize_multipliers = vec![];
 
         // Uses BTreeMap to increase compression ratio.
         let mut char_ngram_weights = BTreeMap::new();
         let mut type_ngram_weights = BTreeMap::new();
+        let mut prev_token_weights = BTreeMap::new();
+        let mut next_token_weights = BTreeMap::new();
 
         let mut class_offset = 0;
         for (i, tag_ids) in tag_ids.iter().enumerate() {
@@ -177,7 +243,9 @@ impl<'a> TagTrainer<'a> {
             }
 
             // train
-            let (feature_ids, xs, ys) = Self::gen_feature_vecs(examples, i, tag_ids);
+            let (feature_ids, mut xs, mut ys, weights) =
+                Self::gen_feature_vecs(examples, i, tag_ids);
+            let cost = replicate_weighted_examples(&mut xs, &mut ys, &weights, cost)?;
 
             let mut builder = liblinear::Builder::new();
             let training_input = liblinear::util::TrainingInput::from_sparse_features(ys, xs)
@@ -203,6 +271,7 @@ impl<'a> TagTrainer<'a> {
                 }
             }
             let quantize_multiplier = weight_max / f64::from((1 << (QUANTIZE_BIT_DEPTH - 1)) - 1);
+            quantize_multipliers.push(quantize_multiplier);
 
             for (i, &cls) in model.labels().iter().enumerate() {
                 bias[class_offset + usize::try_from(cls).unwrap()] = unsafe {
@@ -254,6 +323,29 @@ impl<'a> TagTrainer<'a> {
                                 [class_offset + usize::try_from(cls).unwrap()] = weight;
                         }
                     }
+                    TagFeature::PreviousToken(surface) | TagFeature::NextToken(surface) => {
+                        let context_weights = if matches!(feature, TagFeature::PreviousToken(_)) {
+                            &mut prev_token_weights
+                        } else {
+                            &mut next_token_weights
+                        };
+                        for (i, &cls) in model.labels().iter().enumerate() {
+                            let raw_weight = model.feature_coefficient(
+                                i32::try_from(fid)?,
+                                i32::try_from(i).unwrap(),
+                            );
+                            let weight = unsafe {
+                                (raw_weight / quantize_multiplier).to_int_unchecked::<i32>()
+                            };
+                            if weight == 0 {
+                                continue;
+                            }
+                            context_weights
+                                .entry(*surface)
+                                .or_insert_with(|| vec![0; n_class])
+                                [class_offset + usize::try_from(cls).unwrap()] = weight;
+                        }
+                    }
                 }
             }
             class_offset += tag_ids.len();
@@ -295,10 +387,181 @@ impl<'a> TagTrainer<'a> {
                     .collect(),
             ),
             bias,
+            prev_token_weights: prev_token_weights
+                .into_iter()
+                .map(|(token, weights)| TagContextWeight {
+                    token: token.to_string(),
+                    weights,
+                })
+                .collect(),
+            next_token_weights: next_token_weights
+                .into_iter()
+                .map(|(token, weights)| TagContextWeight {
+                    token: token.to_string(),
+                    weights,
+                })
+                .collect(),
+            quantize_multipliers,
         })
     }
 
-    pub fn train(mut self, epsilon: f64, cost: f64, solver: SolverType) -> Result<Vec<TagModel>> {
+    fn train_oov(
+        examples: &[OovExample<'a>],
+        slots: &[usize],
+        vocabulary: &TagVocabularyBuilder,
+        epsilon: f64,
+        cost: f64,
+        solver: SolverType,
+        mut observer: Option<&mut (dyn TrainingObserver + 'a)>,
+    ) -> Result<OovTagModel> {
+        let n_tags = slots.iter().max().map_or(0, |&i| i + 1);
+        let mut tag_ids = vec![HashMap::new(); n_tags];
+        let mut tags = vec![vec![]; n_tags];
+        for example in examples {
+            for &i in slots {
+                if let Some(tag) = example.tags.get(i).and_then(|tag| tag.as_ref()) {
+                    if !tag_ids[i].contains_key(tag.as_ref()) {
+                        let new_id = tag_ids[i].len();
+                        tag_ids[i].insert(tag.as_ref(), new_id);
+                        tags[i].push(vocabulary.get(tag).unwrap());
+                    }
+                }
+            }
+        }
+        let n_class = tags
+            .iter()
+            .fold(0, |acc, x| acc + if x.len() >= 2 { x.len() } else { 0 });
+
+        // Features do not depend on tags, so they are extracted only once.
+        let mut feature_ids: HashMap<Vec<u8>, u32> = HashMap::new();
+        let mut feature_vecs = vec![];
+        for example in examples {
+            let mut feature_vec = vec![];
+            for_each_oov_feature(example.surface, example.char_types, |key| {
+                let feature_id = if let Some(&feature_id) = feature_ids.get(key) {
+                    feature_id
+                } else {
+                    let new_id = u32::try_from(feature_ids.len() + 1).unwrap();
+                    feature_ids.insert(key.to_vec(), new_id);
+                    new_id
+                };
+                feature_vec.push((feature_id, 1f64));
+            });
+            feature_vecs.push(feature_vec);
+        }
+
+        let mut bias = vec![0; n_class];
+        let mut quantize_multipliers = vec![];
+        // Uses BTreeMap to increase compression ratio.
+        let mut feature_weights = BTreeMap::new();
+
+        let n_slots = tag_ids.iter().filter(|tag_ids| tag_ids.len() >= 2).count();
+        let mut n_trained = 0;
+        if let Some(observer) = observer.as_mut() {
+            observer.notify(TrainingProgress::OovTagTraining { n_trained, n_slots });
+        }
+
+        let mut class_offset = 0;
+        for (i, tag_ids) in tag_ids.iter().enumerate() {
+            if tag_ids.len() <= 1 {
+                // fixed tag or not predicted
+                continue;
+            }
+
+            // train
+            let mut xs = vec![];
+            let mut ys = vec![];
+            let mut weights = vec![];
+            for (example, feature_vec) in examples.iter().zip(&feature_vecs) {
+                if let Some(tag) = example.tags.get(i).and_then(|tag| tag.as_ref()) {
+                    xs.push(feature_vec.clone());
+                    ys.push(tag_ids[tag.as_ref()] as f64);
+                    weights.push(example.weight);
+                }
+            }
+            let cost = replicate_weighted_examples(&mut xs, &mut ys, &weights, cost)?;
+
+            let mut builder = liblinear::Builder::new();
+            let training_input = liblinear::util::TrainingInput::from_sparse_features(ys, xs)
+                .map_err(|e| VaporettoError::invalid_model(format!("liblinear error: {e:?}")))?;
+            builder.problem().input_data(training_input).bias(1.0);
+            builder
+                .parameters()
+                .solver_type(solver.into())
+                .stopping_criterion(epsilon)
+                .constraints_violation_cost(cost);
+            let model = builder
+                .build_model()
+                .map_err(|e| VaporettoError::invalid_model(e.to_string()))?;
+
+            // Calculates the quantize multiplier
+            let mut weight_max = 1e-6f64;
+            for i in 0..i32::try_from(tag_ids.len()).unwrap() {
+                let bias = model.label_bias(i).abs();
+                weight_max = weight_max.max(bias);
+                for fid in 0..model.num_features() {
+                    let weight = model.feature_coefficient(i32::try_from(fid + 1)?, i).abs();
+                    weight_max = weight_max.max(weight);
+                }
+            }
+            let quantize_multiplier = weight_max / f64::from((1 << (QUANTIZE_BIT_DEPTH - 1)) - 1);
+            quantize_multipliers.push(quantize_multiplier);
+
+            for (i, &cls) in model.labels().iter().enumerate() {
+                bias[class_offset + usize::try_from(cls).unwrap()] = unsafe {
+                    (model.label_bias(i32::try_from(i).unwrap()) / quantize_multiplier)
+                        .to_int_unchecked::<i32>()
+                };
+            }
+            for (key, &fid) in &feature_ids {
+                // Features that only appear in examples without this tag are not in the model.
+                if usize::try_from(fid).unwrap() > model.num_features() {
+                    continue;
+                }
+                for (i, &cls) in model.labels().iter().enumerate() {
+                    let raw_weight =
+                        model.feature_coefficient(i32::try_from(fid)?, i32::try_from(i).unwrap());
+                    let weight =
+                        unsafe { (raw_weight / quantize_multiplier).to_int_unchecked::<i32>() };
+                    if weight == 0 {
+                        continue;
+                    }
+                    feature_weights
+                        .entry(key.as_slice())
+                        .or_insert_with(|| vec![0; n_class])
+                        [class_offset + usize::try_from(cls).unwrap()] = weight;
+                }
+            }
+            class_offset += tag_ids.len();
+
+            n_trained += 1;
+            if let Some(observer) = observer.as_mut() {
+                observer.notify(TrainingProgress::OovTagTraining { n_trained, n_slots });
+            }
+        }
+
+        Ok(OovTagModel {
+            tags,
+            bias,
+            weights: feature_weights
+                .into_iter()
+                .map(|(key, weights)| OovTagWeight {
+                    key: key.to_vec(),
+                    weights,
+                })
+                .collect(),
+            quantize_multipliers,
+        })
+    }
+
+    pub fn train(
+        mut self,
+        epsilon: f64,
+        cost: f64,
+        solver: SolverType,
+        n_threads: usize,
+        mut observer: Option<&mut (dyn TrainingObserver + 'a)>,
+    ) -> Result<(Vec<TagModel>, OovTagModel, Vec<String>)> {
         for (token, tags) in self.default_tags {
             if tags.iter().any(|t| t.is_some()) && !self.examples.contains_key(token) {
                 self.examples.insert(
@@ -306,25 +569,168 @@ impl<'a> TagTrainer<'a> {
                     vec![TagExample {
                         tags,
                         features: vec![],
+                        weight: 1.,
                     }],
                 );
             }
         }
+        let examples: Vec<_> = self.examples.into_iter().collect();
+        let n_tokens = examples.len();
+
+        // Tags are shared by all tokens, so they are interned before training.
+        let mut vocabulary = TagVocabularyBuilder::default();
+        for (_, examples) in &examples {
+            for example in examples {
+                for tag in example.tags.iter().flatten() {
+                    vocabulary.intern(tag);
+                }
+            }
+        }
+        for example in &self.oov_examples {
+            for &i in &self.oov_slots {
+                if let Some(tag) = example.tags.get(i).and_then(|tag| tag.as_ref()) {
+                    vocabulary.intern(tag);
+                }
+            }
+        }
+
+        // Each worker takes the next untrained token, and the results are stored at the same
+        // index as the token, so the order of models does not depend on the number of threads.
+        // Solvers consuming the global random number generator are trained in a single thread
+        // because their results depend on the order of training.
+        let n_threads = if solver.uses_random() { 1 } else { n_threads };
         let mut tag_models = vec![];
-        liblinear::toggle_liblinear_stdout_output(false);
-        let n_tokens = self.examples.len();
-        for (i, (token, examples)) in self.examples.into_iter().enumerate() {
-            tag_models.push(Self::train_tag(
-                token.into(),
-                &examples,
+        tag_models.resize_with(n_tokens, || None);
+        let next_idx = AtomicUsize::new(0);
+        let result = thread::scope(|scope| -> Result<()> {
+            let examples = &examples;
+            let vocabulary = &vocabulary;
+            let next_idx = &next_idx;
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..n_threads.min(n_tokens) {
+                let tx = tx.clone();
+                scope.spawn(move || loop {
+                    let i = next_idx.fetch_add(1, Ordering::Relaxed);
+                    let Some((token, examples)) = examples.get(i) else {
+                        break;
+                    };
+                    let result = Self::train_tag(
+                        (*token).into(),
+                        examples,
+                        vocabulary,
+                        epsilon,
+                        cost,
+                        solver,
+                    );
+                    if tx.send((i, result)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+            for (n_trained, (i, result)) in rx.into_iter().enumerate() {
+                // Returning an error here drops the receiver, which stops the other workers.
+                tag_models[i] = Some(result?);
+                if let Some(observer) = observer.as_mut() {
+                    observer.notify(TrainingProgress::TagTraining {
+                        n_trained: n_trained + 1,
+                        n_tokens,
+                    });
+                }
+            }
+            Ok(())
+        });
+        // All models are trained if no worker has failed.
+        result?;
+        let oov_tag_model = if self.oov_slots.is_empty() {
+            OovTagModel::default()
+        } else {
+            Self::train_oov(
+                &self.oov_examples,
+                &self.oov_slots,
+                &vocabulary,
                 epsilon,
                 cost,
                 solver,
-            )?);
-            eprint!("Tags: {i}/{n_tokens}\r");
+                observer,
+            )?
+        };
+        Ok((
+            tag_models.into_iter().map(Option::unwrap).collect(),
+            oov_tag_model,
+            vocabulary.build(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_context_features() {
+        let s =
+            Sentence::from_tokenized("この/連体詞 人/名詞 は/助詞 火星/名詞 人/接尾辞").unwrap();
+        let mut trainer = TagTrainer::new(3, 0, 3, 0, HashMap::new());
+        trainer.set_token_context(true);
+        trainer.add_example(&s, 1.);
+
+        let examples = &trainer.examples["人"];
+        assert_eq!(2, examples.len());
+        assert_eq!(
+            vec![
+                TagFeature::PreviousToken("この"),
+                TagFeature::NextToken("は")
+            ],
+            examples[0].features,
+        );
+        assert_eq!(
+            vec![TagFeature::PreviousToken("火星"), TagFeature::NextToken("")],
+            examples[1].features,
+        );
+        assert_eq!(
+            vec![TagFeature::PreviousToken(""), TagFeature::NextToken("人")],
+            trainer.examples["この"][0].features,
+        );
+    }
+
+    #[test]
+    fn check_context_features_partial() {
+        let s = Sentence::from_partial_annotation("こ-の|人/名詞|は/助詞 火-星|人/接尾辞").unwrap();
+        let mut trainer = TagTrainer::new(3, 0, 3, 0, HashMap::new());
+        trainer.set_token_context(true);
+        trainer.add_example(&s, 1.);
+
+        let examples = &trainer.examples["人"];
+        assert_eq!(2, examples.len());
+        // The end of the next token is unknown.
+        assert_eq!(
+            vec![TagFeature::PreviousToken("この")],
+            examples[0].features
+        );
+        // The start of the previous token is unknown.
+        assert_eq!(vec![TagFeature::NextToken("")], examples[1].features);
+    }
+
+    #[test]
+    fn check_train_error() {
+        let s1 = Sentence::from_tokenized("人/名詞 は/助詞").unwrap();
+        let s2 = Sentence::from_tokenized("火星/名詞 人/接尾辞").unwrap();
+        for n_threads in [1, 2] {
+            let mut trainer = TagTrainer::new(3, 0, 3, 0, HashMap::new());
+            trainer.add_example(&s1, 1.);
+            // The ratio of weights is too large to replicate examples.
+            trainer.add_example(&s2, 1000.);
+
+            assert!(trainer
+                .train(
+                    0.01,
+                    1.,
+                    SolverType::L2RegularizedL2LossSVC,
+                    n_threads,
+                    None
+                )
+                .is_err());
         }
-        eprintln!("Tags: {n_tokens}/{n_tokens}");
-        liblinear::toggle_liblinear_stdout_output(true);
-        Ok(tag_models)
     }
 }
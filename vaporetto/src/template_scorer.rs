@@ -0,0 +1,106 @@
+use alloc::vec::Vec;
+
+use bincode::{de::Decoder, error::DecodeError, Decode, Encode};
+
+use crate::errors::{Result, VaporettoError};
+use crate::feature_template::{FeatureTemplate, TemplateModel};
+use crate::sentence::Sentence;
+use crate::utils::SerializableHashMap;
+
+#[derive(Encode)]
+pub struct TemplateScorer {
+    templates: Vec<FeatureTemplate>,
+    weights: SerializableHashMap<Vec<u8>, i32>,
+}
+
+impl Decode for TemplateScorer {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let templates = Decode::decode(decoder)?;
+        let weights = Decode::decode(decoder)?;
+        Ok(Self { templates, weights })
+    }
+}
+bincode::impl_borrow_decode!(TemplateScorer);
+
+impl TemplateScorer {
+    pub fn new(model: TemplateModel) -> Result<Option<Self>> {
+        if model.templates.is_empty() || model.weights.is_empty() {
+            return Ok(None);
+        }
+        if model.templates.len() > usize::from(u8::MAX) + 1 {
+            return Err(VaporettoError::invalid_model("too many feature templates"));
+        }
+        let mut weights = SerializableHashMap::default();
+        for record in model.weights {
+            if weights.insert(record.key, record.weight).is_some() {
+                return Err(VaporettoError::invalid_model(
+                    "feature template weights contain duplicate keys",
+                ));
+            }
+        }
+        Ok(Some(Self {
+            templates: model.templates,
+            weights,
+        }))
+    }
+
+    pub fn add_scores(&self, sentence: &mut Sentence) {
+        let mut key = vec![];
+        for i in 0..sentence.boundaries.len() {
+            let mut score = 0;
+            // The number of templates is checked in new().
+            for (id, template) in (0..=u8::MAX).zip(&self.templates) {
+                key.clear();
+                template.push_key(id, sentence, i, &mut key);
+                if let Some(&weight) = self.weights.get(key.as_slice()) {
+                    score += weight;
+                }
+            }
+            sentence.boundary_scores[sentence.score_padding + i] += score;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::feature_template::TemplateWeight;
+
+    #[test]
+    fn test_add_scores() {
+        let template: FeatureTemplate = "c-1,c1".parse().unwrap();
+        let mut s = Sentence::from_raw("火星猫だ").unwrap();
+        let mut key_1 = vec![];
+        template.push_key(0, &s, 0, &mut key_1);
+        let mut key_2 = vec![];
+        template.push_key(0, &s, 2, &mut key_2);
+        let scorer = TemplateScorer::new(TemplateModel {
+            templates: vec![template],
+            weights: vec![
+                TemplateWeight {
+                    key: key_1,
+                    weight: 3,
+                },
+                TemplateWeight {
+                    key: key_2,
+                    weight: -5,
+                },
+            ],
+        })
+        .unwrap()
+        .unwrap();
+        s.score_padding = 1;
+        s.boundary_scores = vec![0; 5];
+        scorer.add_scores(&mut s);
+
+        assert_eq!(&[3, 0, -5], s.boundary_scores());
+    }
+
+    #[test]
+    fn test_empty_model() {
+        assert!(TemplateScorer::new(TemplateModel::default())
+            .unwrap()
+            .is_none());
+    }
+}
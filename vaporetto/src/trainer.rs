@@ -9,6 +9,7 @@ use liblinear::LibLinearModel;
 
 use crate::dict_model::{DictModel, WordWeightRecord};
 use crate::errors::{Result, VaporettoError};
+use crate::feature_template::{FeatureTemplate, TemplateModel, TemplateWeight};
 use crate::model::Model;
 use crate::ngram_model::{NgramData, NgramModel};
 use crate::sentence::{CharacterBoundary, Sentence};
@@ -46,6 +47,18 @@ pub enum SolverType {
     L2RegularizedLogisticDual = 7,
 }
 
+impl SolverType {
+    /// Returns `true` if the solver shuffles examples using the random number generator of
+    /// LIBLINEAR. The generator is global, so the results of such solvers depend on the order of
+    /// training.
+    pub(crate) const fn uses_random(self) -> bool {
+        !matches!(
+            self,
+            Self::L2RegularizedLogistic | Self::L2RegularizedL2LossSVC
+        )
+    }
+}
+
 impl FromStr for SolverType {
     type Err = &'static str;
 
@@ -94,6 +107,7 @@ pub enum DictionaryWordPosition {
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub struct DictionaryWordFeature {
+    pub(crate) category: usize,
     pub(crate) length: usize,
     pub(crate) position: DictionaryWordPosition,
 }
@@ -103,6 +117,7 @@ enum BoundaryFeature<'a> {
     CharacterNgram(NgramFeature<&'a str>),
     CharacterTypeNgram(NgramFeature<&'a [u8]>),
     DictionaryWord(DictionaryWordFeature),
+    Template(Vec<u8>),
 }
 
 impl<'a> BoundaryFeature<'a> {
@@ -120,35 +135,166 @@ impl<'a> BoundaryFeature<'a> {
         })
     }
 
-    pub const fn dict_word_left(length: usize) -> Self {
+    pub const fn dict_word(
+        category: usize,
+        length: usize,
+        position: DictionaryWordPosition,
+    ) -> Self {
         Self::DictionaryWord(DictionaryWordFeature {
+            category,
             length,
-            position: DictionaryWordPosition::Left,
+            position,
         })
     }
 
+    #[cfg(test)]
+    pub const fn dict_word_left(length: usize) -> Self {
+        Self::dict_word(0, length, DictionaryWordPosition::Left)
+    }
+
+    #[cfg(test)]
     pub const fn dict_word_inside(length: usize) -> Self {
-        Self::DictionaryWord(DictionaryWordFeature {
-            length,
-            position: DictionaryWordPosition::Inside,
-        })
+        Self::dict_word(0, length, DictionaryWordPosition::Inside)
     }
 
+    #[cfg(test)]
     pub const fn dict_word_right(length: usize) -> Self {
-        Self::DictionaryWord(DictionaryWordFeature {
-            length,
-            position: DictionaryWordPosition::Right,
-        })
+        Self::dict_word(0, length, DictionaryWordPosition::Right)
     }
 }
 
-#[derive(Clone, Copy)]
-struct DummyValue;
+/// Progress of training reported to a [`TrainingObserver`].
+#[cfg_attr(docsrs, doc(cfg(feature = "train")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TrainingProgress {
+    /// A sentence has been converted into features.
+    FeatureExtraction {
+        /// The number of sentences added so far.
+        n_sentences: usize,
+
+        /// The number of boundary features extracted so far.
+        n_features: usize,
+    },
+
+    /// Training of the word boundary model has started.
+    BoundaryTrainingStarted,
+
+    /// Training of the word boundary model has finished.
+    BoundaryTrainingFinished,
+
+    /// A tag model has been trained.
+    TagTraining {
+        /// The number of trained tag models.
+        n_trained: usize,
+
+        /// The number of all tag models.
+        n_tokens: usize,
+    },
+
+    /// A tag slot of the model for out-of-vocabulary tokens has been trained. This is also
+    /// reported with `n_trained` of 0 when training of the model starts.
+    OovTagTraining {
+        /// The number of trained tag slots.
+        n_trained: usize,
+
+        /// The number of all tag slots to be trained.
+        n_slots: usize,
+    },
+}
+
+/// Trait to observe the progress of a [`Trainer`].
+///
+/// The trainer itself does not print anything, so implement this trait to report the progress.
+///
+/// # Examples
+///
+/// ```
+/// use vaporetto::{TrainingObserver, TrainingProgress};
+///
+/// struct TagCounter(usize);
+///
+/// impl TrainingObserver for TagCounter {
+///     fn notify(&mut self, progress: TrainingProgress) {
+///         if let TrainingProgress::TagTraining { n_trained, .. } = progress {
+///             self.0 = n_trained;
+///         }
+///     }
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "train")))]
+pub trait TrainingObserver {
+    /// Receives the progress of training.
+    ///
+    /// This function is always called from the thread that calls the trainer.
+    fn notify(&mut self, progress: TrainingProgress);
+}
+
+/// The maximum number of copies of an example generated by [`replicate_weighted_examples()`].
+const MAX_REPLICATION: f64 = 100.;
+
+/// The maximum relative error of weights caused by rounding the numbers of copies.
+const MAX_ROUNDING_ERROR: f64 = 0.01;
 
-impl From<usize> for DummyValue {
-    fn from(_: usize) -> Self {
-        Self
+/// Replicates examples in proportion to their weights.
+///
+/// LIBLINEAR does not support instance weights, so each example is repeated
+/// `round(weight / unit)` times, and the cost is multiplied by `unit` so that the objective
+/// function stays close to the weighted one. The unit is the largest fraction `min_weight / k`
+/// for which the relative rounding error of every weight is at most 1%. Returns the adjusted
+/// cost, which is `cost` itself if there are no examples.
+///
+/// # Errors
+///
+/// If no unit exists such that each example is repeated at most 100 times, an error variant will
+/// be returned.
+pub(crate) fn replicate_weighted_examples(
+    xs: &mut Vec<Vec<(u32, f64)>>,
+    ys: &mut Vec<f64>,
+    weights: &[f64],
+    cost: f64,
+) -> Result<f64> {
+    if weights.is_empty() {
+        return Ok(cost);
+    }
+    let weight_min = weights.iter().fold(f64::INFINITY, |acc, &w| acc.min(w));
+    let weight_max = weights.iter().fold(0f64, |acc, &w| acc.max(w));
+    if weight_min == weight_max {
+        // No replication is needed if all examples have the same weight.
+        return Ok(cost * weight_min);
+    }
+    let mut k = 1.;
+    let unit = loop {
+        let unit = weight_min / k;
+        if weight_max / unit > MAX_REPLICATION {
+            return Err(VaporettoError::invalid_argument(
+                "weights",
+                "must be multiples of a common unit up to 100 times the unit",
+            ));
+        }
+        if weights.iter().all(|&w| {
+            let n_copies = w / unit;
+            (n_copies.round() - n_copies).abs() <= MAX_ROUNDING_ERROR * n_copies
+        }) {
+            break unit;
+        }
+        k += 1.;
+    };
+    let mut new_xs = vec![];
+    let mut new_ys = vec![];
+    for ((x, y), &w) in xs.drain(..).zip(ys.drain(..)).zip(weights) {
+        let mut n_copies = (w / unit).round();
+        while n_copies > 1. {
+            new_xs.push(x.clone());
+            new_ys.push(y);
+            n_copies -= 1.;
+        }
+        new_xs.push(x);
+        new_ys.push(y);
     }
+    *xs = new_xs;
+    *ys = new_ys;
+    Ok(cost * unit)
 }
 
 /// Trainer.
@@ -185,10 +331,19 @@ pub struct Trainer<'a> {
     type_ngram_size: u8,
     feature_ids: HashMap<BoundaryFeature<'a>, u32>,
     dict_words: Vec<String>,
-    dict_pma: Option<DoubleArrayAhoCorasick<DummyValue>>,
+    // Categories of each dictionary word. The category 0 is used for words without categories.
+    dict_word_categories: Vec<Vec<usize>>,
+    dict_word_ids: HashMap<String, usize>,
+    dict_categories: Vec<String>,
+    dict_pma: Option<DoubleArrayAhoCorasick<u32>>,
     dict_word_max_len: u8,
+    templates: Vec<FeatureTemplate>,
     xs: Vec<Vec<(u32, f64)>>,
     ys: Vec<f64>,
+    weights: Vec<f64>,
+    n_sentences: usize,
+    n_threads: usize,
+    observer: Option<Box<dyn TrainingObserver + 'a>>,
 
     tag_trainer: TagTrainer<'a>,
 }
@@ -228,6 +383,12 @@ impl<'a> Trainer<'a> {
                     .map_err(|e| VaporettoError::invalid_argument("dict_words", e.to_string()))?,
             )
         };
+        let dict_word_categories = vec![vec![0]; dict_words.len()];
+        let dict_word_ids = dict_words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| (word.clone(), i))
+            .collect();
         let mut default_tags = HashMap::new();
         for s in tag_dictionary {
             for token in s.iter_tokens() {
@@ -243,10 +404,18 @@ impl<'a> Trainer<'a> {
             type_ngram_size,
             feature_ids: HashMap::new(),
             dict_words,
+            dict_word_categories,
+            dict_word_ids,
+            dict_categories: vec![String::new()],
             dict_pma,
             dict_word_max_len,
+            templates: vec![],
             xs: vec![],
             ys: vec![],
+            weights: vec![],
+            n_sentences: 0,
+            n_threads: 1,
+            observer: None,
             tag_trainer: TagTrainer::new(
                 char_window_size,
                 char_ngram_size,
@@ -257,6 +426,179 @@ impl<'a> Trainer<'a> {
         })
     }
 
+    /// Adds dictionary words belonging to the specified category.
+    ///
+    /// Dictionary features are generated for each category separately, so words in different
+    /// categories (e.g., a product name dictionary and a general lexicon) receive different
+    /// weights. Words given to [`Trainer::new()`] belong to the default category, which is also
+    /// specified by an empty string. A word can belong to multiple categories, and the weights of
+    /// all its categories are summed up in the model.
+    ///
+    /// This function must be called before adding examples.
+    ///
+    /// # Errors
+    ///
+    /// If examples have already been added, or the dictionary cannot be built, an error variant
+    /// will be returned.
+    pub fn add_dictionary_words<I, S>(&mut self, category: &str, words: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if self.n_sentences != 0 {
+            return Err(VaporettoError::invalid_argument(
+                "words",
+                "dictionary words must be added before examples",
+            ));
+        }
+        let category_id = if let Some(id) = self.dict_categories.iter().position(|c| c == category)
+        {
+            id
+        } else {
+            self.dict_categories.push(category.to_string());
+            self.dict_categories.len() - 1
+        };
+        for word in words {
+            let word = word.into();
+            if let Some(&word_id) = self.dict_word_ids.get(&word) {
+                let categories = &mut self.dict_word_categories[word_id];
+                if !categories.contains(&category_id) {
+                    categories.push(category_id);
+                }
+            } else {
+                self.dict_word_ids
+                    .insert(word.clone(), self.dict_words.len());
+                self.dict_words.push(word);
+                self.dict_word_categories.push(vec![category_id]);
+            }
+        }
+        self.dict_pma = if self.dict_words.is_empty() {
+            None
+        } else {
+            Some(
+                DoubleArrayAhoCorasick::new(&self.dict_words)
+                    .map_err(|e| VaporettoError::invalid_argument("words", e.to_string()))?,
+            )
+        };
+        Ok(())
+    }
+
+    /// Sets feature templates used in addition to the n-gram features.
+    ///
+    /// The templates are stored in the model, and the predictor generates the same features.
+    /// See [`FeatureTemplate`] for details.
+    ///
+    /// This function must be called before adding examples.
+    ///
+    /// # Errors
+    ///
+    /// If examples have already been added, or more than 256 templates are given, an error
+    /// variant will be returned.
+    pub fn set_feature_templates(&mut self, templates: Vec<FeatureTemplate>) -> Result<()> {
+        if self.n_sentences != 0 {
+            return Err(VaporettoError::invalid_argument(
+                "templates",
+                "feature templates must be set before adding examples",
+            ));
+        }
+        if templates.len() > usize::from(u8::MAX) + 1 {
+            return Err(VaporettoError::invalid_argument(
+                "templates",
+                "must not contain more than 256 templates",
+            ));
+        }
+        self.templates = templates;
+        Ok(())
+    }
+
+    /// Enables features of neighboring tokens for tag prediction.
+    ///
+    /// If enabled, the surfaces of the previous and next tokens are used as features of tag
+    /// models in addition to the character and character type n-grams. This helps to distinguish
+    /// homographs whose tags depend on the surrounding words.
+    ///
+    /// This function must be called before adding examples.
+    ///
+    /// # Errors
+    ///
+    /// If examples have already been added, an error variant will be returned.
+    pub fn set_tag_context(&mut self, enabled: bool) -> Result<()> {
+        if self.n_sentences != 0 {
+            return Err(VaporettoError::invalid_argument(
+                "enabled",
+                "tag context must be set before adding examples",
+            ));
+        }
+        self.tag_trainer.set_token_context(enabled);
+        Ok(())
+    }
+
+    /// Enables the tag model for out-of-vocabulary tokens.
+    ///
+    /// Tags are usually predicted only for tokens that appear in the training corpus or the tag
+    /// dictionary. If this option is specified, an additional model is trained on all tokens
+    /// using their prefixes, suffixes, character types, and lengths, and the predictor uses it
+    /// for the other tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `slots` - Indices of tags predicted by the model, e.g., `[0]` for the first tag. Tags
+    ///   with many candidates such as pronunciations are not suitable for this model.
+    ///
+    /// This function must be called before adding examples.
+    ///
+    /// # Errors
+    ///
+    /// If examples have already been added, an error variant will be returned.
+    pub fn set_oov_tag_slots(&mut self, slots: Vec<usize>) -> Result<()> {
+        if self.n_sentences != 0 {
+            return Err(VaporettoError::invalid_argument(
+                "slots",
+                "OOV tag slots must be set before adding examples",
+            ));
+        }
+        self.tag_trainer.set_oov_slots(slots);
+        Ok(())
+    }
+
+    /// Sets the number of threads used to train tag models.
+    ///
+    /// Tag models are trained independently for each token, so they can be trained in parallel.
+    /// The trained models are stored in the same order regardless of the number of threads.
+    ///
+    /// Solvers using random shuffling (dual and L1-regularized ones) share the global random
+    /// number generator of LIBLINEAR, so their results depend on the order of training. To keep
+    /// the model identical to the sequential result, tag models are always trained sequentially
+    /// with these solvers, and this setting only takes effect for
+    /// [`SolverType::L2RegularizedLogistic`] and [`SolverType::L2RegularizedL2LossSVC`].
+    ///
+    /// The default value is 1.
+    ///
+    /// # Errors
+    ///
+    /// If `n_threads` is 0, an error variant will be returned.
+    pub fn set_n_threads(&mut self, n_threads: usize) -> Result<()> {
+        if n_threads == 0 {
+            return Err(VaporettoError::invalid_argument(
+                "n_threads",
+                "must be at least 1",
+            ));
+        }
+        self.n_threads = n_threads;
+        Ok(())
+    }
+
+    /// Sets an observer that receives the progress of training.
+    ///
+    /// The output of the LIBLINEAR solver is suppressed during training regardless of the
+    /// observer.
+    pub fn set_observer<O>(&mut self, observer: O)
+    where
+        O: TrainingObserver + 'a,
+    {
+        self.observer = Some(Box::new(observer));
+    }
+
     fn gen_features<'b>(
         &self,
         sentence: &'a Sentence<'a, 'b>,
@@ -290,6 +632,12 @@ impl<'a> Trainer<'a> {
                     ));
                 }
             }
+            // adds template features
+            for (id, template) in (0..=u8::MAX).zip(&self.templates) {
+                let mut key = vec![];
+                template.push_key(id, sentence, i, &mut key);
+                features.push(BoundaryFeature::Template(key));
+            }
             examples.push((features, b));
         }
         // adds dictionary features
@@ -300,18 +648,28 @@ impl<'a> Trainer<'a> {
                 debug_assert!(sentence.text.is_char_boundary(m.end()));
                 let end = unsafe { sentence.str_to_char_pos(m.end()) };
                 let length = (end - start).min(usize::from(self.dict_word_max_len));
-                if start != 0 {
-                    examples[start - 1]
-                        .0
-                        .push(BoundaryFeature::dict_word_left(length));
-                }
-                for example in &mut examples[start..end - 1] {
-                    example.0.push(BoundaryFeature::dict_word_inside(length));
-                }
-                if end != sentence.len() {
-                    examples[end - 1]
-                        .0
-                        .push(BoundaryFeature::dict_word_right(length));
+                for &category in &self.dict_word_categories[usize::try_from(m.value()).unwrap()] {
+                    if start != 0 {
+                        examples[start - 1].0.push(BoundaryFeature::dict_word(
+                            category,
+                            length,
+                            DictionaryWordPosition::Left,
+                        ));
+                    }
+                    for example in &mut examples[start..end - 1] {
+                        example.0.push(BoundaryFeature::dict_word(
+                            category,
+                            length,
+                            DictionaryWordPosition::Inside,
+                        ));
+                    }
+                    if end != sentence.len() {
+                        examples[end - 1].0.push(BoundaryFeature::dict_word(
+                            category,
+                            length,
+                            DictionaryWordPosition::Right,
+                        ));
+                    }
                 }
             }
         }
@@ -319,9 +677,88 @@ impl<'a> Trainer<'a> {
 
     /// Adds a sentence to the trainer.
     pub fn add_example<'b>(&mut self, sentence: &'a Sentence<'a, 'b>) {
+        self.add_weighted_example(sentence, 1.).unwrap();
+    }
+
+    /// Adds a sentence with a sample weight to the trainer.
+    ///
+    /// The weight is applied to all character boundaries and tags in the sentence.
+    ///
+    /// Since LIBLINEAR does not support instance weights, each example is copied `weight / unit`
+    /// times during training, where the unit is the largest common divisor of all weights within
+    /// an error of 1%. For example, weights of 1 and 1.4 give a unit of 0.2, so every example is
+    /// copied 5 or 7 times, and the memory usage and the training time grow by that factor. Use
+    /// weights with a small integer ratio, such as 1 and 2, to keep the cost low. If some weight
+    /// needs more than 100 copies, training fails.
+    ///
+    /// # Errors
+    ///
+    /// If `weight` is negative or not finite, an error variant will be returned.
+    pub fn add_weighted_example<'b>(
+        &mut self,
+        sentence: &'a Sentence<'a, 'b>,
+        weight: f64,
+    ) -> Result<()> {
+        if !weight.is_finite() || weight < 0. {
+            return Err(VaporettoError::invalid_argument(
+                "weight",
+                "must be a non-negative finite number",
+            ));
+        }
+        if weight != 0. {
+            self.add_boundary_examples(sentence, |_| weight);
+            self.tag_trainer.add_example(sentence, weight);
+        }
+        self.notify_example_added();
+        Ok(())
+    }
+
+    /// Adds a sentence with a sample weight for each character boundary to the trainer.
+    ///
+    /// Boundaries with zero weights are not used for training, so the sentence can be partially
+    /// weighted, e.g., to reduce the weights of boundaries annotated automatically. Tags are added
+    /// with a weight of 1. See [`Trainer::add_weighted_example()`] for the constraints on weights.
+    ///
+    /// # Errors
+    ///
+    /// If the length of `weights` is different from that of [`Sentence::boundaries()`], or a
+    /// weight is negative or not finite, an error variant will be returned.
+    pub fn add_example_with_boundary_weights<'b>(
+        &mut self,
+        sentence: &'a Sentence<'a, 'b>,
+        weights: &[f64],
+    ) -> Result<()> {
+        if weights.len() != sentence.boundaries().len() {
+            return Err(VaporettoError::invalid_argument(
+                "weights",
+                "must have the same length as the boundaries",
+            ));
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.) {
+            return Err(VaporettoError::invalid_argument(
+                "weights",
+                "must be non-negative finite numbers",
+            ));
+        }
+        self.add_boundary_examples(sentence, |i| weights[i]);
+        self.tag_trainer.add_example(sentence, 1.);
+        self.notify_example_added();
+        Ok(())
+    }
+
+    /// Adds examples of character boundaries with weights returned by `weight_fn`. Boundaries with
+    /// zero weights are skipped.
+    fn add_boundary_examples<'b, F>(&mut self, sentence: &'a Sentence<'a, 'b>, weight_fn: F)
+    where
+        F: Fn(usize) -> f64,
+    {
         let mut examples = vec![];
         self.gen_features(sentence, &mut examples);
-        for (features, b) in examples {
+        for (i, (features, b)) in examples.into_iter().enumerate() {
+            let weight = weight_fn(i);
+            if weight == 0. {
+                continue;
+            }
             let mut feature_vector = HashMap::new();
             for feature in features {
                 let new_id = self.feature_ids.len() + 1;
@@ -333,9 +770,18 @@ impl<'a> Trainer<'a> {
             }
             self.xs.push(feature_vector.into_iter().collect());
             self.ys.push(f64::from(b as u8));
+            self.weights.push(weight);
         }
+    }
 
-        self.tag_trainer.add_example(sentence);
+    fn notify_example_added(&mut self) {
+        self.n_sentences += 1;
+        if let Some(observer) = self.observer.as_mut() {
+            observer.notify(TrainingProgress::FeatureExtraction {
+                n_sentences: self.n_sentences,
+                n_features: self.feature_ids.len(),
+            });
+        }
     }
 
     /// Trains word boundaries and tags.
@@ -346,22 +792,38 @@ impl<'a> Trainer<'a> {
     /// * `cost` - The parameter C.
     /// * `solver` - Solver type.
     ///
+    /// If examples have different weights, they are copied before training, which multiplies the
+    /// memory usage and the training time. See [`Trainer::add_weighted_example()`] for details.
+    ///
+    /// The trainer does not change the output setting of liblinear, which prints messages of the
+    /// solver to stdout by default. Call `liblinear::toggle_liblinear_stdout_output()` to suppress
+    /// them.
+    ///
     /// # Errors
     ///
     /// If the solver returns an error, that will be propagated.
-    pub fn train(self, epsilon: f64, cost: f64, solver: SolverType) -> Result<Model> {
+    pub fn train(mut self, epsilon: f64, cost: f64, solver: SolverType) -> Result<Model> {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.notify(TrainingProgress::BoundaryTrainingStarted);
+        }
+        let mut xs = core::mem::take(&mut self.xs);
+        let mut ys = core::mem::take(&mut self.ys);
+        let boundary_cost = replicate_weighted_examples(&mut xs, &mut ys, &self.weights, cost)?;
         let mut builder = liblinear::Builder::new();
-        let training_input = liblinear::util::TrainingInput::from_sparse_features(self.ys, self.xs)
+        let training_input = liblinear::util::TrainingInput::from_sparse_features(ys, xs)
             .map_err(|e| VaporettoError::invalid_model(format!("liblinear error: {e:?}")))?;
         builder.problem().input_data(training_input).bias(1.0);
         builder
             .parameters()
             .solver_type(solver.into())
             .stopping_criterion(epsilon)
-            .constraints_violation_cost(cost);
+            .constraints_violation_cost(boundary_cost);
         let model = builder
             .build_model()
             .map_err(|e| VaporettoError::invalid_model(e.to_string()))?;
+        if let Some(observer) = self.observer.as_mut() {
+            observer.notify(TrainingProgress::BoundaryTrainingFinished);
+        }
 
         let wb_idx = i32::try_from(
             model
@@ -388,7 +850,9 @@ impl<'a> Trainer<'a> {
         // Uses BTreeMap to improve compression ratio.
         let mut char_ngram_weights: BTreeMap<_, Vec<_>> = BTreeMap::new();
         let mut type_ngram_weights: BTreeMap<_, Vec<_>> = BTreeMap::new();
-        let mut dict_weights = vec![(0, 0, 0); usize::from(self.dict_word_max_len)];
+        let mut template_weights = BTreeMap::new();
+        let mut dict_weights =
+            vec![vec![(0, 0, 0); usize::from(self.dict_word_max_len)]; self.dict_categories.len()];
 
         let bias = unsafe { (bias / quantize_multiplier).to_int_unchecked::<i32>() };
 
@@ -435,20 +899,33 @@ impl<'a> Trainer<'a> {
                         type_ngram_weights.insert(ngram.to_vec(), weights);
                     }
                 }
-                BoundaryFeature::DictionaryWord(DictionaryWordFeature { length, position }) => {
-                    let weights = &mut dict_weights[length - 1];
+                BoundaryFeature::DictionaryWord(DictionaryWordFeature {
+                    category,
+                    length,
+                    position,
+                }) => {
+                    let weights = &mut dict_weights[category][length - 1];
                     match position {
                         DictionaryWordPosition::Left => weights.0 = weight,
                         DictionaryWordPosition::Inside => weights.1 = weight,
                         DictionaryWordPosition::Right => weights.2 = weight,
                     }
                 }
+                BoundaryFeature::Template(key) => {
+                    template_weights.insert(key, weight);
+                }
             }
         }
 
-        let tag_models = self.tag_trainer.train(epsilon, cost, solver)?;
+        let (tag_models, oov_tag_model, tag_vocabulary) = self.tag_trainer.train(
+            epsilon,
+            cost,
+            solver,
+            self.n_threads,
+            self.observer.as_deref_mut(),
+        )?;
 
-        Ok(Model::new(
+        let mut model = Model::new(
             NgramModel(
                 char_ngram_weights
                     .into_iter()
@@ -464,17 +941,28 @@ impl<'a> Trainer<'a> {
             DictModel::new(
                 self.dict_words
                     .into_iter()
-                    .map(|word| {
+                    .zip(self.dict_word_categories)
+                    .map(|(word, categories)| {
                         let word_len = word.chars().count();
-                        let idx = word_len.min(dict_weights.len()) - 1;
+                        let idx = word_len.min(usize::from(self.dict_word_max_len)) - 1;
                         let mut weights = vec![0; word_len + 1];
-                        *weights.first_mut().unwrap() = dict_weights[idx].0;
-                        weights[1..word_len].fill(dict_weights[idx].1);
-                        *weights.last_mut().unwrap() = dict_weights[idx].2;
+                        for &category in &categories {
+                            let (left, inside, right) = dict_weights[category][idx];
+                            *weights.first_mut().unwrap() += left;
+                            weights[1..word_len].iter_mut().for_each(|w| *w += inside);
+                            *weights.last_mut().unwrap() += right;
+                        }
+                        // Records the categories for readability of dumped dictionaries.
+                        let comment = categories
+                            .iter()
+                            .map(|&category| self.dict_categories[category].as_str())
+                            .filter(|category| !category.is_empty())
+                            .collect::<Vec<_>>()
+                            .join(",");
                         WordWeightRecord {
                             word,
                             weights,
-                            comment: "".to_string(),
+                            comment,
                         }
                     })
                     .collect(),
@@ -483,7 +971,18 @@ impl<'a> Trainer<'a> {
             self.char_window_size,
             self.type_window_size,
             tag_models,
-        ))
+        );
+        model.0.template_model = TemplateModel {
+            templates: self.templates,
+            weights: template_weights
+                .into_iter()
+                .map(|(key, weight)| TemplateWeight { key, weight })
+                .collect(),
+        };
+        model.0.oov_tag_model = oov_tag_model;
+        model.0.tag_vocabulary = tag_vocabulary;
+        model.0.quantize_multiplier = quantize_multiplier;
+        Ok(model)
     }
 
     /// Returns the number of boundary features.
@@ -865,4 +1364,400 @@ mod tests {
         );
         assert_eq!(NotWordBoundary, examples[6].1);
     }
+
+    #[test]
+    fn check_tag_models_independent_of_n_threads() {
+        let sents = [
+            Sentence::from_tokenized("この/連体詞 人/名詞 は/助詞 火星/名詞 人/接尾辞 です/助動詞")
+                .unwrap(),
+            Sentence::from_tokenized("人/名詞 が/助詞 見る/動詞 星/名詞").unwrap(),
+            Sentence::from_tokenized("火星/名詞 は/助詞 星/名詞 だ/助動詞").unwrap(),
+        ];
+
+        for solver in [
+            SolverType::L2RegularizedLogistic,
+            SolverType::L1RegularizedL2LossSVC,
+        ] {
+            let mut models = vec![];
+            for n_threads in [1, 4] {
+                let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+                trainer.set_n_threads(n_threads).unwrap();
+                for s in &sents {
+                    trainer.add_example(s);
+                }
+                let model = trainer.train(0.01, 1., solver).unwrap();
+                let mut buf = vec![];
+                model.write(&mut buf).unwrap();
+                models.push(buf);
+            }
+
+            assert_eq!(models[0], models[1]);
+        }
+    }
+
+    #[test]
+    fn check_n_threads_zero() {
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+
+        assert!(trainer.set_n_threads(0).is_err());
+    }
+
+    struct ProgressRecorder<'a>(&'a mut Vec<TrainingProgress>);
+
+    impl TrainingObserver for ProgressRecorder<'_> {
+        fn notify(&mut self, progress: TrainingProgress) {
+            self.0.push(progress);
+        }
+    }
+
+    #[test]
+    fn check_observer() {
+        let sents = [
+            Sentence::from_tokenized("火星/名詞 は/助詞 星/名詞").unwrap(),
+            Sentence::from_tokenized("星/名詞 を/助詞 見る/動詞").unwrap(),
+        ];
+        let mut progresses = vec![];
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+        trainer.set_observer(ProgressRecorder(&mut progresses));
+        for s in &sents {
+            trainer.add_example(s);
+        }
+        let n_features = trainer.n_features();
+        trainer
+            .train(0.01, 1., SolverType::L2RegularizedLogistic)
+            .unwrap();
+
+        assert_eq!(9, progresses.len());
+        assert!(matches!(
+            progresses[0],
+            TrainingProgress::FeatureExtraction { n_sentences: 1, .. },
+        ));
+        assert_eq!(
+            TrainingProgress::FeatureExtraction {
+                n_sentences: 2,
+                n_features,
+            },
+            progresses[1],
+        );
+        assert_eq!(TrainingProgress::BoundaryTrainingStarted, progresses[2]);
+        assert_eq!(TrainingProgress::BoundaryTrainingFinished, progresses[3]);
+        for (i, progress) in progresses[4..].iter().enumerate() {
+            assert_eq!(
+                TrainingProgress::TagTraining {
+                    n_trained: i + 1,
+                    n_tokens: 5,
+                },
+                *progress,
+            );
+        }
+    }
+
+    #[test]
+    fn check_observer_oov() {
+        let sents = [
+            Sentence::from_tokenized("火星/名詞/カセー は/助詞/ワ 星/名詞/ホシ").unwrap(),
+            Sentence::from_tokenized("星/名詞/ホシ を/助詞/オ 見る/動詞/ミル").unwrap(),
+        ];
+        let mut progresses = vec![];
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+        trainer.set_oov_tag_slots(vec![0, 1]).unwrap();
+        trainer.set_observer(ProgressRecorder(&mut progresses));
+        for s in &sents {
+            trainer.add_example(s);
+        }
+        trainer
+            .train(0.01, 1., SolverType::L2RegularizedLogistic)
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                TrainingProgress::OovTagTraining {
+                    n_trained: 0,
+                    n_slots: 2,
+                },
+                TrainingProgress::OovTagTraining {
+                    n_trained: 1,
+                    n_slots: 2,
+                },
+                TrainingProgress::OovTagTraining {
+                    n_trained: 2,
+                    n_slots: 2,
+                },
+            ],
+            progresses[progresses.len() - 3..],
+        );
+    }
+
+    #[test]
+    fn check_replicate_weighted_examples() {
+        let mut xs = vec![vec![(1, 1.)], vec![(2, 1.)], vec![(3, 1.)]];
+        let mut ys = vec![0., 1., 0.];
+        let cost = replicate_weighted_examples(&mut xs, &mut ys, &[0.5, 1.5, 1.], 2.).unwrap();
+
+        assert_eq!(
+            vec![
+                vec![(1, 1.)],
+                vec![(2, 1.)],
+                vec![(2, 1.)],
+                vec![(2, 1.)],
+                vec![(3, 1.)],
+                vec![(3, 1.)],
+            ],
+            xs,
+        );
+        assert_eq!(vec![0., 1., 1., 1., 0., 0.], ys);
+        assert_eq!(1., cost);
+    }
+
+    #[test]
+    fn check_replicate_uniformly_weighted_examples() {
+        let mut xs = vec![vec![(1, 1.)], vec![(2, 1.)]];
+        let mut ys = vec![0., 1.];
+        let cost = replicate_weighted_examples(&mut xs, &mut ys, &[0.5, 0.5], 2.).unwrap();
+
+        assert_eq!(vec![vec![(1, 1.)], vec![(2, 1.)]], xs);
+        assert_eq!(vec![0., 1.], ys);
+        assert_eq!(1., cost);
+    }
+
+    #[test]
+    fn check_replicate_fractional_weights() {
+        let mut xs = vec![vec![(1, 1.)], vec![(2, 1.)]];
+        let mut ys = vec![0., 1.];
+        let cost = replicate_weighted_examples(&mut xs, &mut ys, &[1., 1.4], 2.).unwrap();
+
+        assert_eq!(5, xs.iter().filter(|x| x[0].0 == 1).count());
+        assert_eq!(7, xs.iter().filter(|x| x[0].0 == 2).count());
+        assert_eq!(vec![0.; 5], ys[..5]);
+        assert_eq!(vec![1.; 7], ys[5..]);
+        assert!((cost - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_replicate_no_examples() {
+        let mut xs = vec![];
+        let mut ys = vec![];
+        let cost = replicate_weighted_examples(&mut xs, &mut ys, &[], 2.).unwrap();
+
+        assert!(xs.is_empty());
+        assert!(ys.is_empty());
+        assert_eq!(2., cost);
+    }
+
+    #[test]
+    fn check_replicate_too_large_ratio() {
+        let mut xs = vec![vec![(1, 1.)], vec![(2, 1.)]];
+        let mut ys = vec![0., 1.];
+
+        assert!(replicate_weighted_examples(&mut xs, &mut ys, &[1., 1000.], 2.).is_err());
+        // 3 / 2 needs a unit of 1, but the largest weight then needs 199 copies.
+        let mut xs = vec![vec![(1, 1.)], vec![(2, 1.)], vec![(3, 1.)]];
+        let mut ys = vec![0., 1., 0.];
+        assert!(replicate_weighted_examples(&mut xs, &mut ys, &[2., 3., 199.], 2.).is_err());
+    }
+
+    #[test]
+    fn check_boundary_weights() {
+        let s = Sentence::from_tokenized("これ は テスト").unwrap();
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+
+        assert!(trainer
+            .add_example_with_boundary_weights(&s, &[1., 2.])
+            .is_err());
+        assert!(trainer
+            .add_example_with_boundary_weights(&s, &[1., 2., -1., 0., 1.])
+            .is_err());
+        trainer
+            .add_example_with_boundary_weights(&s, &[1., 2., 0., 0., 0.5])
+            .unwrap();
+
+        assert_eq!(vec![1., 2., 0.5], trainer.weights);
+        assert_eq!(vec![0., 1., 0.], trainer.ys);
+    }
+
+    #[test]
+    fn check_invalid_weight() {
+        let s = Sentence::from_tokenized("これ は テスト です").unwrap();
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+
+        assert!(trainer.add_weighted_example(&s, -1.).is_err());
+        assert!(trainer.add_weighted_example(&s, f64::NAN).is_err());
+        assert!(trainer.add_weighted_example(&s, 0.).is_ok());
+        assert_eq!(0, trainer.n_features());
+    }
+
+    #[test]
+    fn check_features_dict_categories() {
+        let s = Sentence::from_tokenized("これ は テスト です").unwrap();
+        let mut trainer = Trainer::new(1, 1, 1, 1, vec!["これ".into()], 4, &[]).unwrap();
+        trainer
+            .add_dictionary_words("katakana", ["テスト"])
+            .unwrap();
+        trainer
+            .add_dictionary_words("hiragana", ["これ", "です"])
+            .unwrap();
+        let mut examples = vec![];
+        trainer.gen_features(&s, &mut examples);
+
+        // こ-れ
+        assert_eq!(
+            vec![
+                BoundaryFeature::char_ngram("こ", -1),
+                BoundaryFeature::char_ngram("れ", 0),
+                BoundaryFeature::type_ngram(&[Hiragana as u8], -1),
+                BoundaryFeature::type_ngram(&[Hiragana as u8], 0),
+                BoundaryFeature::dict_word(0, 2, DictionaryWordPosition::Inside),
+                BoundaryFeature::dict_word(2, 2, DictionaryWordPosition::Inside),
+            ],
+            examples[0].0,
+        );
+
+        // は|テ
+        assert_eq!(
+            vec![
+                BoundaryFeature::char_ngram("は", -1),
+                BoundaryFeature::char_ngram("テ", 0),
+                BoundaryFeature::type_ngram(&[Hiragana as u8], -1),
+                BoundaryFeature::type_ngram(&[Katakana as u8], 0),
+                BoundaryFeature::dict_word(1, 3, DictionaryWordPosition::Left),
+            ],
+            examples[2].0,
+        );
+    }
+
+    #[test]
+    fn check_dictionary_words_after_examples() {
+        let s = Sentence::from_tokenized("これ は テスト です").unwrap();
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 4, &[]).unwrap();
+        trainer.add_example(&s);
+
+        assert!(trainer.add_dictionary_words("", ["テスト"]).is_err());
+    }
+
+    #[test]
+    fn check_features_template() {
+        let s = Sentence::from_tokenized("火星 猫").unwrap();
+        let mut trainer = Trainer::new(1, 1, 1, 1, vec![], 0, &[]).unwrap();
+        trainer
+            .set_feature_templates(vec!["c-2,c0".parse().unwrap(), "t-1".parse().unwrap()])
+            .unwrap();
+        let mut examples = vec![];
+        trainer.gen_features(&s, &mut examples);
+
+        let mut key_0 = vec![0, 0xFF];
+        key_0.extend_from_slice("星".as_bytes());
+        let mut key_1 = vec![0];
+        key_1.extend_from_slice("火猫".as_bytes());
+
+        // 火-星
+        assert_eq!(
+            vec![
+                BoundaryFeature::char_ngram("火", -1),
+                BoundaryFeature::char_ngram("星", 0),
+                BoundaryFeature::type_ngram(&[Kanji as u8], -1),
+                BoundaryFeature::type_ngram(&[Kanji as u8], 0),
+                BoundaryFeature::Template(key_0),
+                BoundaryFeature::Template(vec![1, Kanji as u8]),
+            ],
+            examples[0].0,
+        );
+
+        // 星|猫
+        assert_eq!(
+            vec![
+                BoundaryFeature::char_ngram("星", -1),
+                BoundaryFeature::char_ngram("猫", 0),
+                BoundaryFeature::type_ngram(&[Kanji as u8], -1),
+                BoundaryFeature::type_ngram(&[Kanji as u8], 0),
+                BoundaryFeature::Template(key_1),
+                BoundaryFeature::Template(vec![1, Kanji as u8]),
+            ],
+            examples[1].0,
+        );
+    }
+
+    #[test]
+    fn check_feature_templates_after_examples() {
+        let s = Sentence::from_tokenized("これ は テスト です").unwrap();
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 4, &[]).unwrap();
+        trainer.add_example(&s);
+
+        assert!(trainer
+            .set_feature_templates(vec!["c-1,c0".parse().unwrap()])
+            .is_err());
+    }
+
+    #[test]
+    fn check_oov_tag_model() {
+        let sents = [
+            Sentence::from_tokenized("火星/名詞/カセー は/助詞/ワ 星/名詞/ホシ").unwrap(),
+            Sentence::from_tokenized("星/名詞/ホシ を/助詞/オ 見る/動詞/ミル").unwrap(),
+        ];
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+        trainer.set_oov_tag_slots(vec![0]).unwrap();
+        for s in &sents {
+            trainer.add_example(s);
+        }
+        let model = trainer
+            .train(0.01, 1., SolverType::L2RegularizedLogistic)
+            .unwrap();
+
+        let oov_tag_model = &model.0.oov_tag_model;
+        assert_eq!(1, oov_tag_model.tags.len());
+        assert_eq!(
+            vec!["名詞", "助詞", "動詞"],
+            oov_tag_model.tags[0]
+                .iter()
+                .map(|&id| model.tag_vocabulary()[usize::try_from(id).unwrap()].as_str())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(3, oov_tag_model.bias.len());
+        assert!(oov_tag_model.weights.iter().all(|w| w.weights.len() == 3));
+    }
+
+    #[test]
+    fn check_tag_vocabulary() {
+        let sents = [
+            Sentence::from_tokenized("火星/名詞 は/助詞 星/名詞").unwrap(),
+            Sentence::from_tokenized("星/名詞 を/助詞 見る/動詞").unwrap(),
+        ];
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+        for s in &sents {
+            trainer.add_example(s);
+        }
+        let model = trainer
+            .train(0.01, 1., SolverType::L2RegularizedLogistic)
+            .unwrap();
+
+        // Each tag is stored only once.
+        let mut vocabulary = model.tag_vocabulary().to_vec();
+        vocabulary.sort_unstable();
+        assert_eq!(vec!["助詞", "動詞", "名詞"], vocabulary);
+        for tag_model in model.tag_models() {
+            let tag = match tag_model.token() {
+                "火星" | "星" => "名詞",
+                "は" | "を" => "助詞",
+                _ => "動詞",
+            };
+            assert_eq!(
+                vec![tag],
+                tag_model.tag_ids()[0]
+                    .iter()
+                    .map(|&id| model.tag_vocabulary()[usize::try_from(id).unwrap()].as_str())
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+
+    #[test]
+    fn check_no_oov_tag_model() {
+        let s = Sentence::from_tokenized("火星/名詞 は/助詞 星/名詞").unwrap();
+        let mut trainer = Trainer::new(3, 3, 3, 3, vec![], 0, &[]).unwrap();
+        trainer.add_example(&s);
+        let model = trainer
+            .train(0.01, 1., SolverType::L2RegularizedLogistic)
+            .unwrap();
+
+        assert!(model.0.oov_tag_model.tags.is_empty());
+    }
 }
@@ -0,0 +1,352 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "tag-prediction")]
+use alloc::borrow::Cow;
+
+use bincode::{
+    de::BorrowDecoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    BorrowDecode, Decode, Encode,
+};
+use daachorse::{DoubleArrayAhoCorasick, DoubleArrayAhoCorasickBuilder, MatchKind};
+use hashbrown::HashSet;
+
+use crate::errors::{Result, VaporettoError};
+use crate::sentence::{CharacterBoundary, Sentence};
+
+/// Word of a user dictionary.
+#[derive(Clone, Debug)]
+pub struct UserWord {
+    pub(crate) word: String,
+    pub(crate) tags: Vec<Option<String>>,
+}
+
+impl UserWord {
+    /// Creates a new word of a user dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - A word.
+    /// * `tags` - Tags annotated to the word. `None` elements are predicted by the model.
+    ///
+    /// # Errors
+    ///
+    /// If `word` is empty, an error variant will be returned.
+    pub fn new(word: String, tags: Vec<Option<String>>) -> Result<Self> {
+        if word.is_empty() {
+            return Err(VaporettoError::invalid_argument(
+                "word",
+                "must not be empty",
+            ));
+        }
+        Ok(Self { word, tags })
+    }
+
+    /// Gets a reference to the word.
+    pub fn get_word(&self) -> &str {
+        &self.word
+    }
+
+    /// Gets a reference to the tags.
+    pub fn get_tags(&self) -> &[Option<String>] {
+        &self.tags
+    }
+}
+
+/// Strategy to resolve overlapping matches of a user dictionary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The longest word is selected from words starting at the same position.
+    LongestMatch,
+
+    /// The word that appears first in the dictionary is selected from words starting at the same
+    /// position.
+    FirstMatch,
+}
+
+/// User dictionary whose words are forced to be single tokens.
+///
+/// Matches are searched from the beginning of the text, and each match is skipped if it overlaps
+/// with the previous match.
+pub struct UserDictionary {
+    pma: DoubleArrayAhoCorasick<u32>,
+    tags: Vec<Vec<Option<String>>>,
+    n_tags: usize,
+}
+
+impl<'de> BorrowDecode<'de> for UserDictionary {
+    /// WARNING: This function is inherently unsafe. Do not publish this function outside this
+    /// crate.
+    fn borrow_decode<D: BorrowDecoder<'de>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let pma_data: &[u8] = BorrowDecode::borrow_decode(decoder)?;
+        let (pma, _) = unsafe { DoubleArrayAhoCorasick::deserialize_unchecked(pma_data) };
+        Ok(Self {
+            pma,
+            tags: Decode::decode(decoder)?,
+            n_tags: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for UserDictionary {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        let pma_data = self.pma.serialize();
+        Encode::encode(&pma_data, encoder)?;
+        Encode::encode(&self.tags, encoder)?;
+        Encode::encode(&self.n_tags, encoder)?;
+        Ok(())
+    }
+}
+
+impl UserDictionary {
+    /// Creates a new user dictionary.
+    ///
+    /// If the same word appears more than once, only the first one is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Words of the dictionary.
+    /// * `resolution` - Strategy to resolve overlapping matches.
+    ///
+    /// # Errors
+    ///
+    /// If the dictionary is empty or the automaton cannot be built, an error variant will be
+    /// returned.
+    pub fn new<I>(words: I, resolution: ConflictResolution) -> Result<Self>
+    where
+        I: IntoIterator<Item = UserWord>,
+    {
+        let mut seen = HashSet::new();
+        let mut patterns = vec![];
+        let mut tags = vec![];
+        let mut n_tags = 0;
+        for word in words {
+            if !seen.insert(word.word.clone()) {
+                continue;
+            }
+            let id = u32::try_from(tags.len()).map_err(|_| {
+                VaporettoError::invalid_argument("words", "contains too many words")
+            })?;
+            patterns.push((word.word, id));
+            n_tags = n_tags.max(word.tags.len());
+            tags.push(word.tags);
+        }
+        if patterns.is_empty() {
+            return Err(VaporettoError::invalid_argument(
+                "words",
+                "must not be empty",
+            ));
+        }
+        let match_kind = match resolution {
+            ConflictResolution::LongestMatch => MatchKind::LeftmostLongest,
+            ConflictResolution::FirstMatch => MatchKind::LeftmostFirst,
+        };
+        let pma = DoubleArrayAhoCorasickBuilder::new()
+            .match_kind(match_kind)
+            .build_with_values(patterns)
+            .map_err(|_| {
+                VaporettoError::invalid_argument("words", "failed to build the automaton")
+            })?;
+        Ok(Self { pma, tags, n_tags })
+    }
+
+    /// Returns the number of words.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Returns `true` if the dictionary contains no words.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Returns the maximum number of tags.
+    pub const fn n_tags(&self) -> usize {
+        self.n_tags
+    }
+
+    /// Forces boundaries of matched words.
+    ///
+    /// A match is skipped if it conflicts with fixed boundaries, i.e., a boundary inside the word
+    /// is fixed to [`CharacterBoundary::WordBoundary`] or an edge of the word is fixed to
+    /// [`CharacterBoundary::NotWordBoundary`]. If `only_unknown` is `true`, boundaries other than
+    /// [`CharacterBoundary::Unknown`] are fixed. Otherwise, constraints of the sentence are fixed.
+    pub(crate) fn force_boundaries(&self, sentence: &mut Sentence, only_unknown: bool) {
+        for m in self.pma.leftmost_find_iter(sentence.text.as_bytes()) {
+            debug_assert!(sentence.text.is_char_boundary(m.start()));
+            debug_assert!(sentence.text.is_char_boundary(m.end()));
+            let start = unsafe { sentence.str_to_char_pos(m.start()) };
+            let end = unsafe { sentence.str_to_char_pos(m.end()) };
+            let range_start = start.saturating_sub(1);
+            let range_end = end.min(sentence.boundaries.len());
+            let word_boundary = |i: usize| {
+                if i + 1 == start || i + 1 == end {
+                    CharacterBoundary::WordBoundary
+                } else {
+                    CharacterBoundary::NotWordBoundary
+                }
+            };
+            let fixed = if only_unknown {
+                &sentence.boundaries
+            } else {
+                &sentence.constraints
+            };
+            let conflicts = (range_start..range_end).any(|i| {
+                fixed.get(i).map_or(false, |&b| {
+                    b != CharacterBoundary::Unknown && b != word_boundary(i)
+                })
+            });
+            if conflicts {
+                continue;
+            }
+            for (i, b) in
+                (range_start..range_end).zip(&mut sentence.boundaries[range_start..range_end])
+            {
+                *b = word_boundary(i);
+            }
+        }
+    }
+
+    /// Overwrites tags of matched words that are tokens of the sentence.
+    #[cfg(feature = "tag-prediction")]
+    pub(crate) fn fill_tags<'a>(&'a self, sentence: &mut Sentence<'_, 'a>) {
+        let n_tags = sentence.n_tags;
+        for m in self.pma.leftmost_find_iter(sentence.text.as_bytes()) {
+            let start = unsafe { sentence.str_to_char_pos(m.start()) };
+            let end = unsafe { sentence.str_to_char_pos(m.end()) };
+            let is_token = (start == 0
+                || sentence.boundaries[start - 1] == CharacterBoundary::WordBoundary)
+                && (end == sentence.len()
+                    || sentence.boundaries[end - 1] == CharacterBoundary::WordBoundary)
+                && sentence.boundaries[start..end - 1]
+                    .iter()
+                    .all(|&b| b == CharacterBoundary::NotWordBoundary);
+            if !is_token {
+                continue;
+            }
+            let word_tags = &self.tags[usize::try_from(m.value()).unwrap()];
+            let range = (end - 1) * n_tags..end * n_tags;
+            let tags = &mut sentence.tags[range.clone()];
+            let tag_ids = &mut sentence.tag_ids[range];
+            for ((tag, tag_id), word_tag) in tags.iter_mut().zip(tag_ids).zip(word_tags) {
+                if let Some(word_tag) = word_tag {
+                    tag.replace(Cow::Borrowed(word_tag));
+                    // The tag is not predicted, so it has no ID.
+                    tag_id.take();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::CharacterBoundary::*;
+
+    fn word(w: &str) -> UserWord {
+        UserWord::new(w.into(), vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_force_boundaries_longest() {
+        let dict = UserDictionary::new(
+            vec![word("東京"), word("東京タワー"), word("ワー")],
+            ConflictResolution::LongestMatch,
+        )
+        .unwrap();
+        let mut s = Sentence::from_raw("東京タワーです").unwrap();
+        dict.force_boundaries(&mut s, false);
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                Unknown,
+            ],
+            s.boundaries(),
+        );
+    }
+
+    #[test]
+    fn test_force_boundaries_first() {
+        let dict = UserDictionary::new(
+            vec![word("東京"), word("東京タワー"), word("タワー")],
+            ConflictResolution::FirstMatch,
+        )
+        .unwrap();
+        let mut s = Sentence::from_raw("東京タワーです").unwrap();
+        dict.force_boundaries(&mut s, false);
+        assert_eq!(
+            &[
+                NotWordBoundary,
+                WordBoundary,
+                NotWordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                Unknown,
+            ],
+            s.boundaries(),
+        );
+    }
+
+    #[test]
+    fn test_force_boundaries_only_unknown() {
+        let dict =
+            UserDictionary::new(vec![word("京タ")], ConflictResolution::LongestMatch).unwrap();
+        let mut s = Sentence::from_partial_annotation("東 京|タ ワ ー で す").unwrap();
+        dict.force_boundaries(&mut s, true);
+        // The word crosses an annotated boundary, so it is skipped.
+        assert_eq!(
+            &[Unknown, WordBoundary, Unknown, Unknown, Unknown, Unknown],
+            s.boundaries(),
+        );
+
+        let mut s = Sentence::from_partial_annotation("東-京 タ ワ ー で す").unwrap();
+        dict.force_boundaries(&mut s, true);
+        // The edge of the word is annotated as a non-boundary, so it is skipped.
+        assert_eq!(
+            &[NotWordBoundary, Unknown, Unknown, Unknown, Unknown, Unknown],
+            s.boundaries(),
+        );
+
+        let mut s = Sentence::from_partial_annotation("東|京-タ ワ ー で す").unwrap();
+        dict.force_boundaries(&mut s, true);
+        assert_eq!(
+            &[
+                WordBoundary,
+                NotWordBoundary,
+                WordBoundary,
+                Unknown,
+                Unknown,
+                Unknown
+            ],
+            s.boundaries(),
+        );
+    }
+
+    #[test]
+    fn test_duplicated_words() {
+        let dict = UserDictionary::new(
+            vec![
+                UserWord::new("東京".into(), vec![Some("名詞".into())]).unwrap(),
+                UserWord::new("東京".into(), vec![None, Some("トーキョー".into())]).unwrap(),
+            ],
+            ConflictResolution::LongestMatch,
+        )
+        .unwrap();
+        assert_eq!(1, dict.len());
+        assert_eq!(1, dict.n_tags());
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(UserWord::new("".into(), vec![]).is_err());
+        assert!(UserDictionary::new(vec![], ConflictResolution::LongestMatch).is_err());
+    }
+}
@@ -52,17 +52,32 @@
 extern crate alloc;
 
 pub mod sentence_filters;
+pub mod sentence_splitters;
 pub mod string_filters;
 
 use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
 
 use vaporetto::Sentence;
 
 pub trait SentenceFilter: Send + Sync {
     /// Filter a specified sentence using rules.
+    ///
+    /// Filters that modify boundaries keep constraints given by
+    /// [`Sentence::force_token()`](vaporetto::Sentence::force_token) and
+    /// [`Sentence::force_boundary()`](vaporetto::Sentence::force_boundary).
     fn filter(&self, sentence: &mut Sentence);
 }
 
+pub trait SentenceSplitter: Send + Sync {
+    /// Splits a specified text into sentences using rules.
+    ///
+    /// Returns byte ranges of the sentences in the text. Whitespace around each sentence is not
+    /// included, and empty sentences are omitted.
+    fn split(&self, text: &str) -> Vec<Range<usize>>;
+}
+
 pub trait StringFilter<S>: Send + Sync
 where
     S: AsRef<str>,
@@ -31,6 +31,7 @@ impl SentenceFilter for ConcatGraphemeClustersFilter {
                 start = end;
             }
         }
+        sentence.apply_constraints();
     }
 }
 
@@ -80,4 +81,15 @@ mod tests {
         s.write_tokenized_text(&mut buf);
         assert_eq!("これ は 手 \u{1f44f}\u{1f3fd} で す", buf);
     }
+
+    #[test]
+    fn test_concat_grapheme_clusters_forced() {
+        let mut s = Sentence::from_tokenized("\u{1f44f} \u{1f3fd}").unwrap();
+        s.force_boundary(4).unwrap();
+        let filter = ConcatGraphemeClustersFilter;
+        filter.filter(&mut s);
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+        assert_eq!("\u{1f44f} \u{1f3fd}", buf);
+    }
 }
@@ -40,6 +40,7 @@ impl SentenceFilter for KyteaWsConstFilter {
                 }
             }
         }
+        sentence.apply_constraints();
     }
 }
 
@@ -78,4 +79,15 @@ mod tests {
         s.write_tokenized_text(&mut buf);
         assert_eq!("2021 年 8 月 24 日", buf);
     }
+
+    #[test]
+    fn test_concat_cons_char_types_forced() {
+        let mut s = Sentence::from_tokenized("20 21 年 8 月 2 4 日").unwrap();
+        s.force_boundary(2).unwrap();
+        let filter = KyteaWsConstFilter::new(CharacterType::Digit);
+        filter.filter(&mut s);
+        let mut buf = String::new();
+        s.write_tokenized_text(&mut buf);
+        assert_eq!("20 21 年 8 月 24 日", buf);
+    }
 }
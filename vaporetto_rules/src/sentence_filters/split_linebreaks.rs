@@ -33,6 +33,7 @@ impl SentenceFilter for SplitLinebreaksFilter {
                 i += 1;
             }
         }
+        sentence.apply_constraints();
     }
 }
 
@@ -0,0 +1,5 @@
+//! Splitters of texts into sentences.
+
+mod punctuation;
+
+pub use punctuation::PunctuationSplitter;
@@ -0,0 +1,256 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::SentenceSplitter;
+
+/// Pairs of opening and closing brackets and quotation marks.
+const BRACKETS: &[(char, char)] = &[
+    ('「', '」'),
+    ('『', '』'),
+    ('（', '）'),
+    ('(', ')'),
+    ('【', '】'),
+    ('〔', '〕'),
+    ('［', '］'),
+    ('[', ']'),
+    ('｛', '｝'),
+    ('{', '}'),
+    ('〈', '〉'),
+    ('《', '》'),
+    ('“', '”'),
+    ('‘', '’'),
+    ('"', '"'),
+];
+
+/// Sentence splitter based on punctuation marks.
+///
+/// A sentence ends with a sequence of terminators, such as `。`, `！`, and `？`, followed by
+/// closing brackets and quotation marks. Terminators inside brackets or quotation marks do not
+/// end sentences, so a quoted utterance such as `「はい。そうです。」と答えた。` is kept in a
+/// single sentence. Only brackets closed in the same line are considered, so an unmatched
+/// bracket or quotation mark does not prevent the following text from being split. Line breaks
+/// always end sentences.
+///
+/// # Examples
+///
+/// ```
+/// use vaporetto_rules::{sentence_splitters::PunctuationSplitter, SentenceSplitter};
+///
+/// let text = "火星に行きました。「寒い！」と言った。\n猫もいます";
+/// let splitter = PunctuationSplitter::default();
+/// let sentences: Vec<_> = splitter.split(text).into_iter().map(|r| &text[r]).collect();
+/// assert_eq!(
+///     vec!["火星に行きました。", "「寒い！」と言った。", "猫もいます"],
+///     sentences,
+/// );
+/// ```
+#[derive(Clone)]
+pub struct PunctuationSplitter {
+    terminators: Vec<char>,
+}
+
+impl Default for PunctuationSplitter {
+    /// Creates a new splitter with terminators `。`, `！`, `？`, `!`, and `?`.
+    fn default() -> Self {
+        Self::new(['。', '！', '？', '!', '?'])
+    }
+}
+
+impl PunctuationSplitter {
+    /// Creates a new PunctuationSplitter.
+    ///
+    /// # Arguments
+    ///
+    /// * `terminators` - Characters that end sentences.
+    ///
+    /// # Returns
+    ///
+    /// A new PunctuationSplitter.
+    pub fn new<I>(terminators: I) -> Self
+    where
+        I: IntoIterator<Item = char>,
+    {
+        Self {
+            terminators: terminators.into_iter().collect(),
+        }
+    }
+
+    /// Returns `true` if the character only closes brackets. Symmetric quotation marks are not
+    /// included because they open new quotations at the beginning of sentences.
+    #[inline]
+    fn is_closing(c: char) -> bool {
+        BRACKETS
+            .iter()
+            .any(|&(open, close)| close == c && open != c)
+    }
+
+    /// Returns pairs of byte positions of opening and closing brackets matched in the same line,
+    /// sorted by the opening positions. Unmatched brackets between a matched pair are ignored.
+    fn matched_brackets(text: &str) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        // Pairs of expected closing brackets and positions of the opening brackets.
+        let mut stack: Vec<(char, usize)> = vec![];
+        for (i, c) in text.char_indices() {
+            if c == '\r' || c == '\n' {
+                stack.clear();
+            } else if let Some(j) = stack.iter().rposition(|&(close, _)| close == c) {
+                pairs.push((stack[j].1, i));
+                stack.truncate(j);
+            } else if let Some(&(_, close)) = BRACKETS.iter().find(|&&(open, _)| open == c) {
+                stack.push((close, i));
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+impl SentenceSplitter for PunctuationSplitter {
+    fn split(&self, text: &str) -> Vec<Range<usize>> {
+        let mut sentences = vec![];
+        let pairs = Self::matched_brackets(text);
+        // Positions of closing brackets expected to appear.
+        let mut stack = vec![];
+        let mut start = None;
+        let mut end = 0;
+        let mut terminated = false;
+        for (i, c) in text.char_indices() {
+            if c == '\r' || c == '\n' {
+                if let Some(start) = start.take() {
+                    sentences.push(start..end);
+                }
+                stack.clear();
+                terminated = false;
+                continue;
+            }
+            if terminated && !self.terminators.contains(&c) && !Self::is_closing(c) {
+                if let Some(start) = start.take() {
+                    sentences.push(start..end);
+                }
+                terminated = false;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+            start.get_or_insert(i);
+            end = i + c.len_utf8();
+            if stack.last() == Some(&i) {
+                stack.pop();
+            } else if let Ok(j) = pairs.binary_search_by_key(&i, |&(open, _)| open) {
+                stack.push(pairs[j].1);
+            } else if stack.is_empty() && self.terminators.contains(&c) {
+                terminated = true;
+            }
+        }
+        if let Some(start) = start {
+            sentences.push(start..end);
+        }
+        sentences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split<'a>(splitter: &PunctuationSplitter, text: &'a str) -> Vec<&'a str> {
+        splitter
+            .split(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_split_terminators() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(
+            vec!["本当？！", "はい。。。", "Yes!", "そうです"],
+            split(&splitter, "本当？！はい。。。 Yes! そうです"),
+        );
+    }
+
+    #[test]
+    fn test_split_brackets() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(
+            vec![
+                "彼は「行こう。すぐに！」と言った。",
+                "(笑)「うん。」と頷いた。",
+                "\"Go!\" he said.",
+            ],
+            split(
+                &splitter,
+                "彼は「行こう。すぐに！」と言った。(笑)「うん。」と頷いた。\"Go!\" he said."
+            ),
+        );
+    }
+
+    #[test]
+    fn test_split_closing_after_terminator() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(vec!["終わり。）", "次"], split(&splitter, "終わり。）次"));
+    }
+
+    #[test]
+    fn test_split_linebreaks() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(
+            vec!["「閉じない", "次の行", "最後の行"],
+            split(&splitter, "  「閉じない\r\n次の行\n\n最後の行\n"),
+        );
+    }
+
+    #[test]
+    fn test_split_unmatched_brackets() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(
+            vec!["「はい。", "そうです。", "次の文。"],
+            split(&splitter, "「はい。そうです。次の文。"),
+        );
+        assert_eq!(
+            vec!["彼は\"はい。", "そうです。"],
+            split(&splitter, "彼は\"はい。そうです。"),
+        );
+        assert_eq!(
+            vec!["‘はい。", "そうです。"],
+            split(&splitter, "‘はい。そうです。"),
+        );
+        assert_eq!(vec!["はい）。", "次。"], split(&splitter, "はい）。次。"));
+    }
+
+    #[test]
+    fn test_split_mismatched_brackets() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(
+            vec!["「あ（い。」う。", "え。"],
+            split(&splitter, "「あ（い。」う。え。"),
+        );
+        assert_eq!(
+            vec!["「あ「い。」う。", "え。"],
+            split(&splitter, "「あ「い。」う。え。"),
+        );
+    }
+
+    #[test]
+    fn test_split_offsets() {
+        let splitter = PunctuationSplitter::default();
+        assert_eq!(vec![1..10, 11..20], splitter.split(" 火星。 猫だ。 "));
+    }
+
+    #[test]
+    fn test_split_custom_terminators() {
+        let splitter = PunctuationSplitter::new(['.']);
+        assert_eq!(
+            vec!["Hello.", "How are you?"],
+            split(&splitter, "Hello. How are you?"),
+        );
+    }
+
+    #[test]
+    fn test_split_empty() {
+        let splitter = PunctuationSplitter::default();
+        assert!(splitter.split("").is_empty());
+        assert!(splitter.split(" \n\t").is_empty());
+    }
+}